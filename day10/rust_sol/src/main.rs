@@ -1,64 +1,429 @@
-use std::fs;
+use std::{
+    collections::{HashMap, HashSet},
+    env, fmt, fs,
+};
 
-fn main() {
-    let contents = fs::read_to_string("input.txt").expect("Couldn't read file.");
-    let mut cycle = 0;
-    let mut reg_x = 1;
-    let mut signal_stren_total = 0;
-
-    println!("Answer 2: \n");
-
-    contents.lines().for_each(|line| {
-        let (instruction, value) = parse_line(line);
-        match instruction {
-            "noop" => {
-                cycle += 1;
-                draw_crt(cycle, reg_x);
-                update_noop(cycle, reg_x, &mut signal_stren_total);
+/// To add an instruction, give it a variant here and fill in `parse`,
+/// `cycles` and `execute`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Instruction {
+    Noop,
+    Addx(i32),
+    Mulx(i32),
+    Jmp(i32),
+}
+
+impl Instruction {
+    fn parse(line: &str) -> Instruction {
+        let (name, value) = parse_line(line);
+
+        match name {
+            "noop" => Instruction::Noop,
+            "addx" => Instruction::Addx(value),
+            "mulx" => Instruction::Mulx(value),
+            "jmp" => Instruction::Jmp(value),
+            _ => panic!("Unknown instruction: {}", line),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Instruction::Noop => "noop",
+            Instruction::Addx(_) => "addx",
+            Instruction::Mulx(_) => "mulx",
+            Instruction::Jmp(_) => "jmp",
+        }
+    }
+
+    fn cycles(&self) -> usize {
+        match self {
+            Instruction::Noop => 1,
+            Instruction::Addx(_) => 2,
+            Instruction::Mulx(_) => 3,
+            Instruction::Jmp(_) => 1,
+        }
+    }
+
+    /// Applies the instruction once all of its cycles are done.
+    fn execute(&self, cpu: &mut Cpu) {
+        match self {
+            Instruction::Noop => cpu.pc += 1,
+            Instruction::Addx(value) => {
+                cpu.reg_x += value;
+                cpu.pc += 1;
+            }
+            Instruction::Mulx(value) => {
+                cpu.reg_x *= value;
+                cpu.pc += 1;
+            }
+            Instruction::Jmp(offset) => {
+                cpu.pc = (cpu.pc as i64 + *offset as i64)
+                    .try_into()
+                    .expect("Jumped before the start of the program.");
+            }
+        }
+    }
+}
+
+struct Cpu {
+    program: Vec<Instruction>,
+    pc: usize,
+    reg_x: i32,
+    cycle: usize,
+    remaining: usize,
+    breakpoints: HashSet<usize>,
+}
+
+impl Cpu {
+    fn new(program: Vec<Instruction>) -> Cpu {
+        Cpu {
+            program,
+            pc: 0,
+            reg_x: 1,
+            cycle: 0,
+            remaining: 0,
+            breakpoints: HashSet::new(),
+        }
+    }
+
+    fn load(contents: &str) -> Cpu {
+        Cpu::new(assemble(contents))
+    }
+
+    fn add_breakpoint(&mut self, cycle: usize) {
+        self.breakpoints.insert(cycle);
+    }
+
+    /// Ticks until a breakpoint cycle is reached, returning the state during
+    /// that cycle. Returns `None` once the program ends.
+    fn run_to_breakpoint(&mut self) -> Option<(usize, i32)> {
+        let breakpoints = self.breakpoints.clone();
+        self.find(|(cycle, _)| breakpoints.contains(cycle))
+    }
+
+    /// Runs a single cycle, returning the cycle number, the value of X during
+    /// it and the instruction that's executing.
+    fn step(&mut self) -> Option<(usize, i32, Instruction)> {
+        if self.remaining == 0 {
+            self.remaining = self.program.get(self.pc)?.cycles();
+        }
+
+        self.cycle += 1;
+        self.remaining -= 1;
+        let instruction = self.program[self.pc];
+        let during = (self.cycle, self.reg_x, instruction);
+
+        if self.remaining == 0 {
+            instruction.execute(self);
+        }
+
+        Some(during)
+    }
+
+    /// Prints every cycle until the program ends. Jumps can loop forever, so
+    /// the trace stops after `max_cycles`.
+    fn trace(&mut self, max_cycles: usize) {
+        println!("cycle | instruction |   X | sprite");
+
+        while let Some((cycle, reg_x, instruction)) = self.step() {
+            println!(
+                "{:>5} | {:<11} | {:>3} | {}..{}",
+                cycle,
+                instruction.to_string(),
+                reg_x,
+                reg_x - 1,
+                reg_x + 1
+            );
+
+            if cycle >= max_cycles {
+                break;
+            }
+        }
+    }
+}
+
+/// Each tick yields the cycle number and the value of X *during* that cycle.
+impl Iterator for Cpu {
+    type Item = (usize, i32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step().map(|(cycle, reg_x, _)| (cycle, reg_x))
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "{}", self.name()),
+            Instruction::Addx(value) | Instruction::Mulx(value) | Instruction::Jmp(value) => {
+                write!(f, "{} {}", self.name(), value)
             }
-            "addx" => {
-                cycle += 2;
-                draw_crt(cycle - 1, reg_x);
-                draw_crt(cycle, reg_x);
-                update_add(cycle, reg_x, &mut signal_stren_total);
-                reg_x += value;
+        }
+    }
+}
+
+/// Assembles source in the puzzle's format, with a few extras: `;` starts a
+/// comment, `name:` defines a label (alone or before an instruction) and
+/// `jmp name` jumps to it.
+fn assemble(source: &str) -> Vec<Instruction> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+
+    source.lines().enumerate().for_each(|(line_no, line)| {
+        let mut code = line.split(';').next().unwrap().trim();
+
+        if let Some((label, rest)) = code.split_once(':') {
+            let label = label.trim();
+            if labels.insert(label, statements.len()).is_some() {
+                panic!("line {}: duplicate label {}", line_no + 1, label);
             }
-            _ => (),
+            code = rest.trim();
+        }
+
+        if !code.is_empty() {
+            statements.push((line_no, code));
         }
     });
-    println!("");
-    println!("Answer 1: {signal_stren_total}");
+
+    statements
+        .iter()
+        .enumerate()
+        .map(|(index, &(line_no, code))| match code.split_once(' ') {
+            Some(("jmp", target)) if target.trim().parse::<i32>().is_err() => {
+                let target = labels
+                    .get(target.trim())
+                    .unwrap_or_else(|| panic!("line {}: unknown label {}", line_no + 1, target));
+                Instruction::Jmp(*target as i32 - index as i32)
+            }
+            _ => Instruction::parse(code),
+        })
+        .collect()
 }
 
-fn update_noop(cycle: i32, reg_x: i32, signal_stren_total: &mut i32) {
-    if (cycle - 20) % 40 == 0 {
-        *signal_stren_total += cycle * reg_x;
+fn disassemble(program: &[Instruction]) -> String {
+    program
+        .iter()
+        .map(|instruction| instruction.to_string() + "\n")
+        .collect()
+}
+
+/// Usage: rust_sol [asm | disasm | trace | draw] [file]
+///
+/// With no mode, solves the puzzle for the file (`input.txt` by default).
+/// `draw` reads a 40x6 image of `#` and `.` and prints a program drawing it.
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (mode, path) = match args.as_slice() {
+        [] => ("solve", "input.txt"),
+        [path] => ("solve", path.as_str()),
+        [mode, path, ..] => (mode.as_str(), path.as_str()),
+    };
+    let contents = fs::read_to_string(path).expect("Couldn't read file.");
+
+    match mode {
+        "solve" => {
+            println!("Answer 1: {}", signal_strength(&contents));
+            let crt = Crt::from_ticks(Cpu::load(&contents));
+            println!("Answer 2: {}\n\n{}", crt.read_letters(), crt);
+        }
+        "asm" => {
+            let crt = Crt::from_ticks(Cpu::new(assemble(&contents)));
+            println!("{}\n{}", crt, crt.read_letters());
+        }
+        "disasm" => print!("{}", disassemble(&assemble(&contents))),
+        "trace" => Cpu::new(assemble(&contents)).trace(10_000),
+        "draw" => {
+            let image = Crt::parse(&contents);
+            let program = synthesize(&image).expect("This image can't be drawn.");
+            assert!(Crt::from_ticks(Cpu::new(program.clone())) == image);
+            print!("{}", disassemble(&program));
+        }
+        _ => panic!("Unknown mode: {}", mode),
     }
 }
 
-fn update_add(cycle: i32, reg_x: i32, signal_stren_total: &mut i32) {
-    let prev_cycle = cycle - 1;
+fn signal_strength(contents: &str) -> i32 {
+    let mut cpu = Cpu::load(contents);
+    (20..=220)
+        .step_by(40)
+        .for_each(|cycle| cpu.add_breakpoint(cycle));
 
-    if (cycle - 20) % 40 == 0 {
-        *signal_stren_total += cycle * reg_x;
-    } else if (prev_cycle - 20) % 40 == 0 {
-        *signal_stren_total += prev_cycle * reg_x;
+    let mut total = 0;
+    while let Some((cycle, reg_x)) = cpu.run_to_breakpoint() {
+        total += cycle as i32 * reg_x;
     }
+    total
+}
+
+const CRT_WIDTH: usize = 40;
+const CRT_HEIGHT: usize = 6;
+const GLYPH_WIDTH: usize = 4;
+
+/// The standard AoC capital letters, 4x6 pixels each, read row by row.
+const GLYPHS: [(char, &str); 18] = [
+    ('A', ".##.#..##..######..##..#"),
+    ('B', "###.#..####.#..##..####."),
+    ('C', ".##.#..##...#...#..#.##."),
+    ('E', "#####...###.#...#...####"),
+    ('F', "#####...###.#...#...#..."),
+    ('G', ".##.#..##...#.###..#.###"),
+    ('H', "#..##..######..##..##..#"),
+    ('I', ".###..#...#...#...#..###"),
+    ('J', "..##...#...#...##..#.##."),
+    ('K', "#..##.#.##..#.#.#.#.#..#"),
+    ('L', "#...#...#...#...#...####"),
+    ('O', ".##.#..##..##..##..#.##."),
+    ('P', "###.#..##..####.#...#..."),
+    ('R', "###.#..##..####.#.#.#..#"),
+    ('S', ".####...#....##....####."),
+    ('U', "#..##..##..##..##..#.##."),
+    ('Z', "####...#..#..#..#...####"),
+    (' ', "........................"),
+];
+
+#[derive(PartialEq)]
+struct Crt {
+    pixels: [[bool; CRT_WIDTH]; CRT_HEIGHT],
 }
 
-fn draw_crt(cycle: i32, reg_x: i32) {
-    let col = (cycle - 1) % 40;
-    let (left, mid, right) = (reg_x - 1, reg_x, reg_x + 1);
+impl Crt {
+    fn parse(image: &str) -> Crt {
+        let mut pixels = [[false; CRT_WIDTH]; CRT_HEIGHT];
+
+        image
+            .lines()
+            .take(CRT_HEIGHT)
+            .enumerate()
+            .for_each(|(row, line)| {
+                line.chars()
+                    .take(CRT_WIDTH)
+                    .enumerate()
+                    .for_each(|(col, pixel)| {
+                        pixels[row][col] = pixel == '#';
+                    });
+            });
 
-    if col == left || col == mid || col == right {
-        print!("#");
-    } else {
-        print!(".");
+        Crt { pixels }
     }
 
-    if col == 39 {
-        println!("");
+    fn from_ticks(ticks: impl Iterator<Item = (usize, i32)>) -> Crt {
+        let mut pixels = [[false; CRT_WIDTH]; CRT_HEIGHT];
+
+        ticks
+            .take(CRT_WIDTH * CRT_HEIGHT)
+            .for_each(|(cycle, reg_x)| {
+                let (row, col) = ((cycle - 1) / CRT_WIDTH, (cycle - 1) % CRT_WIDTH);
+                pixels[row][col] = (reg_x - 1..=reg_x + 1).contains(&(col as i32));
+            });
+
+        Crt { pixels }
     }
+
+    /// Letters sit every 5 columns (4 pixels plus a blank gap). Anything that
+    /// isn't in the glyph table reads as `?`.
+    fn read_letters(&self) -> String {
+        (0..CRT_WIDTH / (GLYPH_WIDTH + 1))
+            .map(|letter| {
+                let left = letter * (GLYPH_WIDTH + 1);
+                let glyph: String = self
+                    .pixels
+                    .iter()
+                    .flat_map(|row| row[left..left + GLYPH_WIDTH].iter())
+                    .map(|&lit| if lit { '#' } else { '.' })
+                    .collect();
+
+                GLYPHS
+                    .iter()
+                    .find(|(_, pattern)| *pattern == glyph)
+                    .map_or('?', |(letter, _)| *letter)
+            })
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+}
+
+impl fmt::Display for Crt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.pixels.iter().try_for_each(|row| {
+            let line: String = row.iter().map(|&lit| if lit { '#' } else { '.' }).collect();
+            writeln!(f, "{}", line)
+        })
+    }
+}
+
+/// Finds a program of `addx` and `noop` that draws the image. X only changes
+/// when an `addx` finishes, so it has to hold each value for at least two
+/// cycles; a DP over (cycle, X, held long enough) finds values that light
+/// exactly the right pixels, which then turn into noops and addx.
+fn synthesize(image: &Crt) -> Option<Vec<Instruction>> {
+    // X values outside this range light nothing, so one of them is enough
+    const MIN_X: i32 = -2;
+    const MAX_X: i32 = CRT_WIDTH as i32 + 1;
+    let values = (MAX_X - MIN_X + 1) as usize;
+    let cycles = CRT_WIDTH * CRT_HEIGHT;
+
+    let fits = |cycle: usize, reg_x: i32| {
+        let (row, col) = (cycle / CRT_WIDTH, cycle % CRT_WIDTH);
+        image.pixels[row][col] == (reg_x - 1..=reg_x + 1).contains(&(col as i32))
+    };
+    let index = |reg_x: i32| (reg_x - MIN_X) as usize;
+
+    // reachable[cycle][x][held]: X is x during the cycle and `held` says whether
+    // it's been held for two or more cycles. The value is the previous state.
+    let mut reachable = vec![vec![[None; 2]; values]; cycles];
+    if !fits(0, 1) {
+        return None;
+    }
+    reachable[0][index(1)][0] = Some((1, 0));
+
+    for cycle in 1..cycles {
+        for prev in MIN_X..=MAX_X {
+            for held in 0..2 {
+                if reachable[cycle - 1][index(prev)][held].is_none() {
+                    continue;
+                }
+
+                if fits(cycle, prev) {
+                    reachable[cycle][index(prev)][1] = Some((prev, held));
+                }
+
+                if held == 1 {
+                    (MIN_X..=MAX_X)
+                        .filter(|&next| next != prev && fits(cycle, next))
+                        .for_each(|next| reachable[cycle][index(next)][0] = Some((prev, held)));
+                }
+            }
+        }
+    }
+
+    // walk back to recover X for every cycle
+    let mut state = (MIN_X..=MAX_X)
+        .flat_map(|reg_x| (0..2).map(move |held| (reg_x, held)))
+        .find(|&(reg_x, held)| reachable[cycles - 1][index(reg_x)][held].is_some())?;
+    let mut xs = vec![0; cycles];
+
+    for cycle in (0..cycles).rev() {
+        xs[cycle] = state.0;
+        state = reachable[cycle][index(state.0)][state.1].unwrap();
+    }
+
+    let mut program = Vec::new();
+    let mut start = 0;
+    (1..=cycles).for_each(|cycle| {
+        if cycle == cycles || xs[cycle] != xs[cycle - 1] {
+            let run = cycle - start;
+            if cycle == cycles {
+                program.extend(vec![Instruction::Noop; run]);
+            } else {
+                program.extend(vec![Instruction::Noop; run - 2]);
+                program.push(Instruction::Addx(xs[cycle] - xs[cycle - 1]));
+            }
+            start = cycle;
+        }
+    });
+
+    Some(program)
 }
 
 fn parse_line(line: &str) -> (&str, i32) {
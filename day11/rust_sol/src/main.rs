@@ -1,88 +1,493 @@
-use std::{fs, vec};
+use num_bigint::BigUint;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, VecDeque},
+    env, fs,
+    iter::Peekable,
+    path::Path,
+    vec,
+};
 
-struct Monkey {
-    items: Vec<u128>,
+struct Monkey<T> {
+    items: VecDeque<T>,
     operation: Operation,
     test: u128,
     true_throw: usize,
     false_throw: usize,
     inspected_items: u128,
+    true_throws: u128,
 }
 
-struct Operation {
-    operator: String,
-    operand: Option<u128>,
+/// What happened over a game: inspections per monkey, how many items each
+/// monkey held at the end of every round and how often each threw to each.
+struct Stats {
+    inspected: Vec<u128>,
+    held: Vec<Vec<usize>>,
+    throws: Vec<Vec<u128>>,
 }
 
-impl Monkey {
-    fn operate(&self, input: u128) -> u128 {
-        match (&self.operation.operator[..], self.operation.operand) {
-            (_, None) => input.clone() * input.clone(),
-            ("*", Some(n)) => input * n,
-            ("+", Some(n)) => input + n,
-            (_, _) => panic!("unsuported operation."),
+/// A worry level. `u128` relies on reducing by the product of the tests to
+/// stay small, `BigUint` keeps the exact value.
+trait Worry: Clone {
+    fn from_u128(n: u128) -> Self;
+    /// With a modulo the operands are already reduced, so subtraction wraps
+    /// around it instead of going negative.
+    fn apply(&self, operator: char, other: &Self, modulo: Option<u128>) -> Self;
+    fn is_divisible_by(&self, n: u128) -> bool;
+    fn reduce(&self, modulo: u128) -> Self;
+    fn relieve(&self) -> Self;
+}
+
+impl Worry for u128 {
+    fn from_u128(n: u128) -> Self {
+        n
+    }
+
+    fn apply(&self, operator: char, other: &Self, modulo: Option<u128>) -> Self {
+        match operator {
+            '-' if modulo.is_some() => {
+                let modulo = modulo.unwrap();
+                (self % modulo + modulo - other % modulo) % modulo
+            }
+            '+' => self
+                .checked_add(*other)
+                .expect("worry level overflowed, try --bigint."),
+            '-' => self
+                .checked_sub(*other)
+                .expect("worry level went negative."),
+            '*' => self
+                .checked_mul(*other)
+                .expect("worry level overflowed, try --bigint."),
+            '/' => self
+                .checked_div(*other)
+                .expect("division by zero in operation."),
+            _ => unreachable!(),
         }
     }
 
-    /// Returns item value (modded to keep small) and index of recieving monkey.
-    fn inspect_item(&mut self, worried: bool, modulo: u128) -> (u128, usize) {
-        let mut item = self.items.remove(0);
-        item = self.operate(item);
+    fn is_divisible_by(&self, n: u128) -> bool {
+        self.is_multiple_of(n)
+    }
+
+    fn reduce(&self, modulo: u128) -> Self {
+        self % modulo
+    }
+
+    fn relieve(&self) -> Self {
+        self / 3
+    }
+}
+
+impl Worry for BigUint {
+    fn from_u128(n: u128) -> Self {
+        BigUint::from(n)
+    }
+
+    fn apply(&self, operator: char, other: &Self, modulo: Option<u128>) -> Self {
+        match operator {
+            '-' if modulo.is_some() => {
+                let modulo = BigUint::from(modulo.unwrap());
+                (self % &modulo + &modulo - other % &modulo) % &modulo
+            }
+            '+' => self + other,
+            '-' if other > self => panic!("worry level went negative."),
+            '-' => self - other,
+            '*' => self * other,
+            '/' if *other == BigUint::from(0u8) => panic!("division by zero in operation."),
+            '/' => self / other,
+            _ => unreachable!(),
+        }
+    }
+
+    fn is_divisible_by(&self, n: u128) -> bool {
+        self % n == BigUint::from(0u8)
+    }
+
+    fn reduce(&self, modulo: u128) -> Self {
+        self % modulo
+    }
+
+    fn relieve(&self) -> Self {
+        self / 3u8
+    }
+}
+
+/// The right hand side of `new = <expr>`.
+enum Operation {
+    Old,
+    Const(u128),
+    Binary(Box<Operation>, char, Box<Operation>),
+}
+
+impl Operation {
+    fn eval<T: Worry>(&self, old: &T, modulo: Option<u128>) -> T {
+        match self {
+            Operation::Old => old.clone(),
+            Operation::Const(n) => T::from_u128(*n),
+            Operation::Binary(left, operator, right) => {
+                let right = right.eval(old, modulo);
+                left.eval(old, modulo).apply(*operator, &right, modulo)
+            }
+        }
+    }
+
+    /// Whether results mod m only depend on `old` mod m. Everything but
+    /// division survives reducing first.
+    fn is_modular(&self) -> bool {
+        match self {
+            Operation::Old | Operation::Const(_) => true,
+            Operation::Binary(left, operator, right) => {
+                *operator != '/' && left.is_modular() && right.is_modular()
+            }
+        }
+    }
+}
+
+impl<T: Worry> Monkey<T> {
+    fn operate(&self, input: &T, modulo: Option<u128>) -> T {
+        self.operation.eval(input, modulo)
+    }
+
+    /// Returns item value (modded to keep small if there's a modulo) and index
+    /// of recieving monkey.
+    fn inspect_item(&mut self, worried: bool, modulo: Option<u128>) -> (T, usize) {
+        let item = self.items.pop_front().unwrap();
         self.inspected_items += 1;
 
+        let (item, recvr) = self.throw(&item, worried, modulo);
+        if recvr == self.true_throw {
+            self.true_throws += 1;
+        }
+        (item, recvr)
+    }
+
+    /// Works out where an item goes without touching the monkey's queue.
+    fn throw(&self, item: &T, worried: bool, modulo: Option<u128>) -> (T, usize) {
+        let mut item = self.operate(item, modulo);
+
         if !worried {
-            item /= 3;
+            item = item.relieve();
         }
 
-        if item % self.test == 0 {
-            (item % modulo, self.true_throw)
+        let recvr = if item.is_divisible_by(self.test) {
+            self.true_throw
         } else {
-            (item % modulo, self.false_throw)
+            self.false_throw
+        };
+
+        match modulo {
+            Some(modulo) => (item.reduce(modulo), recvr),
+            None => (item, recvr),
         }
     }
 
-    fn recieve_item(&mut self, item: u128) {
-        self.items.push(item);
+    fn recieve_item(&mut self, item: T) {
+        self.items.push_back(item);
+    }
+}
+
+impl Stats {
+    fn inspections_csv(&self) -> String {
+        let mut csv = String::from("monkey,inspected\n");
+        self.inspected
+            .iter()
+            .enumerate()
+            .for_each(|(monkey, count)| csv += &format!("{},{}\n", monkey, count));
+        csv
+    }
+
+    fn held_csv(&self) -> String {
+        let header: Vec<String> = (0..self.inspected.len())
+            .map(|monkey| format!("monkey {}", monkey))
+            .collect();
+        let mut csv = format!("round,{}\n", header.join(","));
+
+        self.held.iter().enumerate().for_each(|(round, held)| {
+            let held: Vec<String> = held.iter().map(|count| count.to_string()).collect();
+            csv += &format!("{},{}\n", round + 1, held.join(","));
+        });
+        csv
+    }
+
+    /// Rows are the throwing monkey, columns the catching one.
+    fn throws_csv(&self) -> String {
+        let header: Vec<String> = (0..self.throws.len()).map(|m| m.to_string()).collect();
+        let mut csv = format!("from\\to,{}\n", header.join(","));
+
+        self.throws.iter().enumerate().for_each(|(from, row)| {
+            let row: Vec<String> = row.iter().map(|count| count.to_string()).collect();
+            csv += &format!("{},{}\n", from, row.join(","));
+        });
+        csv
+    }
+
+    fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph throws {\n");
+
+        self.inspected
+            .iter()
+            .enumerate()
+            .for_each(|(monkey, count)| {
+                dot += &format!(
+                    "    {} [label=\"Monkey {}\\n{} inspected\"];\n",
+                    monkey, monkey, count
+                );
+            });
+        self.throws.iter().enumerate().for_each(|(from, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, &count)| count > 0)
+                .for_each(|(to, count)| {
+                    dot += &format!("    {} -> {} [label=\"{}\"];\n", from, to, count);
+                });
+        });
+
+        dot + "}\n"
+    }
+
+    fn print_report(&self) {
+        println!("monkey | inspected | held at end");
+        self.inspected
+            .iter()
+            .enumerate()
+            .for_each(|(monkey, count)| {
+                let held = self.held.last().map_or(0, |held| held[monkey]);
+                println!("{:>6} | {:>9} | {:>11}", monkey, count, held);
+            });
+
+        println!("\nthrows (rows throw to columns)");
+        print!("{}", self.throws_csv().replace(',', "\t"));
+    }
+
+    fn export(&self, dir: &str) -> std::io::Result<()> {
+        let dir = Path::new(dir);
+        fs::create_dir_all(dir)?;
+
+        fs::write(dir.join("inspections.csv"), self.inspections_csv())?;
+        fs::write(dir.join("held.csv"), self.held_csv())?;
+        fs::write(dir.join("throws.csv"), self.throws_csv())?;
+        fs::write(dir.join("throws.dot"), self.to_dot())
     }
 }
 
+/// Usage: rust_sol [--rounds <n>] [--bigint [rounds]] [--stats <rounds>]
+///                 [--export <dir>]
+///
+/// `--rounds` sets how many rounds the worried game (part 2) lasts.
+/// `--bigint` plays the worried game for a few rounds (20 by default) with
+/// exact worry levels and checks the modulo fast path gives the same answer.
+/// `--stats` reports per-monkey statistics for the worried game, and
+/// `--export` also writes them out as CSV files and a Graphviz graph.
 fn main() {
     let contents = fs::read_to_string("input.txt").expect("Couldn't read file.");
+    let mut args = env::args().skip(1).peekable();
+    let mut rounds = 10000;
+    let mut bigint_rounds = None;
+    let mut stats_rounds = None;
+    let mut export_dir = None;
 
-    let mut monkeys = build_monkeys(&contents);
-    let mut angry_monkeys = build_monkeys(&contents);
-    let modulo = get_modulo(&mut angry_monkeys);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rounds" => {
+                rounds = args
+                    .next()
+                    .and_then(|rounds| rounds.parse().ok())
+                    .expect("--rounds needs a round count.");
+            }
+            "--bigint" => {
+                bigint_rounds = Some(
+                    args.next_if(|rounds| rounds.parse::<u64>().is_ok())
+                        .map_or(20, |rounds| rounds.parse().unwrap()),
+                );
+            }
+            "--stats" => {
+                stats_rounds = args.next().and_then(|rounds| rounds.parse().ok());
+                assert!(stats_rounds.is_some(), "--stats needs a round count.");
+            }
+            "--export" => export_dir = Some(args.next().expect("--export needs a directory.")),
+            _ => panic!("Unknown argument: {}", arg),
+        }
+    }
 
-    (0..20).for_each(|_| play_round(&mut monkeys, false, modulo));
-    (0..10000).for_each(|_| play_round(&mut angry_monkeys, true, modulo));
+    if let Some(rounds) = stats_rounds {
+        let stats = gather_stats(&contents, rounds, true);
+        stats.print_report();
 
-    monkeys.sort_by(|m1, m2| m2.inspected_items.cmp(&m1.inspected_items));
-    angry_monkeys.sort_by(|m1, m2| m2.inspected_items.cmp(&m1.inspected_items));
+        if let Some(dir) = export_dir {
+            stats.export(&dir).expect("Couldn't export stats.");
+        }
+        return;
+    }
 
-    println!(
-        "Answer 1: {}",
-        monkeys[0].inspected_items * monkeys[1].inspected_items
-    );
-    println!(
-        "Answer 2: {}",
-        angry_monkeys[0].inspected_items * angry_monkeys[1].inspected_items
-    );
+    match bigint_rounds {
+        Some(rounds) => {
+            let exact = monkey_business::<BigUint>(&contents, rounds, true, None);
+            println!("bigint: {}", exact);
+
+            if modulo_is_valid(&build_monkeys::<u128>(&contents), true) {
+                let fast = solve(&contents, rounds, true);
+                println!("modulo: {}", fast);
+                println!("{}", if exact == fast { "match" } else { "MISMATCH" });
+            } else {
+                println!("modulo: skipped, the operations aren't modular");
+            }
+        }
+        None => {
+            println!("Answer 1: {}", solve(&contents, 20, false));
+            println!("Answer 2: {}", solve(&contents, rounds, true));
+        }
+    }
+}
+
+/// Uses the modulo trick when it gives the same answer, and exact worry
+/// levels otherwise.
+fn solve(contents: &str, rounds: u64, worried: bool) -> u128 {
+    let monkeys = build_monkeys::<u128>(contents);
+
+    if modulo_is_valid(&monkeys, worried) {
+        item_business(&monkeys, rounds, get_modulo(&monkeys))
+    } else {
+        if worried {
+            eprintln!("Operations aren't modular, falling back to exact worry levels.");
+        }
+        monkey_business::<BigUint>(contents, rounds, worried, None)
+    }
+}
+
+/// Dividing by 3 for relief doesn't commute with reducing mod m either.
+fn modulo_is_valid<T>(monkeys: &[Monkey<T>], worried: bool) -> bool {
+    worried && monkeys.iter().all(|m| m.operation.is_modular())
+}
+
+fn gather_stats(contents: &str, rounds: u64, worried: bool) -> Stats {
+    let monkeys = build_monkeys::<u128>(contents);
+
+    if modulo_is_valid(&monkeys, worried) {
+        let modulo = get_modulo(&monkeys);
+        stats_for(monkeys, rounds, worried, Some(modulo))
+    } else {
+        stats_for(build_monkeys::<BigUint>(contents), rounds, worried, None)
+    }
+}
+
+fn stats_for<T: Worry>(
+    mut monkeys: Vec<Monkey<T>>,
+    rounds: u64,
+    worried: bool,
+    modulo: Option<u128>,
+) -> Stats {
+    let mut held = vec![];
+
+    (0..rounds).for_each(|_| {
+        play_round(&mut monkeys, worried, modulo);
+        held.push(monkeys.iter().map(|m| m.items.len()).collect());
+    });
+
+    let mut throws = vec![vec![0; monkeys.len()]; monkeys.len()];
+    monkeys.iter().enumerate().for_each(|(from, m)| {
+        throws[from][m.true_throw] += m.true_throws;
+        throws[from][m.false_throw] += m.inspected_items - m.true_throws;
+    });
+
+    Stats {
+        inspected: monkeys.iter().map(|m| m.inspected_items).collect(),
+        held,
+        throws,
+    }
+}
+
+fn monkey_business<T: Worry>(
+    contents: &str,
+    rounds: u64,
+    worried: bool,
+    modulo: Option<u128>,
+) -> u128 {
+    let mut monkeys = build_monkeys::<T>(contents);
+
+    (0..rounds).for_each(|_| play_round(&mut monkeys, worried, modulo));
+    monkeys.sort_by_key(|m| Reverse(m.inspected_items));
+
+    monkeys[0].inspected_items * monkeys[1].inspected_items
+}
+
+/// Follows every item on its own, since an item's path only depends on its
+/// own worry level. Once a (monkey, worry) pair repeats at the start of a
+/// round the inspections repeat too, so the rest can be skipped.
+fn item_business(monkeys: &[Monkey<u128>], rounds: u64, modulo: u128) -> u128 {
+    let mut inspected = vec![0; monkeys.len()];
+
+    monkeys.iter().enumerate().for_each(|(holder, monkey)| {
+        monkey.items.iter().for_each(|&item| {
+            count_inspections(monkeys, holder, item, rounds, modulo)
+                .iter()
+                .zip(inspected.iter_mut())
+                .for_each(|(count, total)| *total += count);
+        });
+    });
+
+    inspected.sort_by_key(|&count| Reverse(count));
+    inspected[0] * inspected[1]
+}
+
+/// How many times each monkey inspects one item over the given rounds.
+fn count_inspections(
+    monkeys: &[Monkey<u128>],
+    mut holder: usize,
+    mut item: u128,
+    rounds: u64,
+    modulo: u128,
+) -> Vec<u128> {
+    let mut seen = HashMap::new();
+    // history[r] holds the counts before round r
+    let mut history = vec![vec![0; monkeys.len()]];
+
+    for round in 0..rounds as usize {
+        if let Some(start) = seen.insert((holder, item), round) {
+            let cycle = round - start;
+            let full_cycles = (rounds - round as u64) / cycle as u64;
+            let leftover = (rounds - round as u64) as usize % cycle;
+            let (before, now, partial) =
+                (&history[start], &history[round], &history[start + leftover]);
+
+            return (0..monkeys.len())
+                .map(|m| {
+                    now[m] + (now[m] - before[m]) * full_cycles as u128 + partial[m] - before[m]
+                })
+                .collect();
+        }
+
+        // the round ends once the item is thrown back to an earlier monkey
+        let mut counts = history[round].clone();
+        loop {
+            counts[holder] += 1;
+            let (next_item, recvr) = monkeys[holder].throw(&item, true, Some(modulo));
+            let round_over = recvr <= holder;
+            (item, holder) = (next_item, recvr);
+
+            if round_over {
+                break;
+            }
+        }
+        history.push(counts);
+    }
+
+    history.pop().unwrap()
 }
 
-fn get_modulo(agro_monkeys: &mut [Monkey]) -> u128 {
+fn get_modulo<T>(agro_monkeys: &[Monkey<T>]) -> u128 {
     agro_monkeys.iter().map(|m| m.test).product()
 }
 
-fn play_round(monkeys: &mut [Monkey], worried: bool, test: u128) {
+fn play_round<T: Worry>(monkeys: &mut [Monkey<T>], worried: bool, modulo: Option<u128>) {
     (0..monkeys.len()).for_each(|i| {
         (0..monkeys[i].items.len()).for_each(|_| {
-            let (item, recvr) = monkeys[i].inspect_item(worried, test);
+            let (item, recvr) = monkeys[i].inspect_item(worried, modulo);
             monkeys[recvr].recieve_item(item);
         });
     });
 }
 
-fn build_monkeys(contents: &String) -> Vec<Monkey> {
+fn build_monkeys<T: Worry>(contents: &str) -> Vec<Monkey<T>> {
     let mut lines = vec![];
     let mut monkeys = vec![];
 
@@ -97,12 +502,9 @@ fn build_monkeys(contents: &String) -> Vec<Monkey> {
     monkeys
 }
 
-fn build_monkey(lines: &[&str]) -> Monkey {
+fn build_monkey<T: Worry>(lines: &[&str]) -> Monkey<T> {
     let mut items = vec![];
-    let mut operation = Operation {
-        operator: String::new(),
-        operand: None,
-    };
+    let mut operation = Operation::Old;
     let (mut test, mut true_throw, mut false_throw) = (0, 0, 0);
 
     lines.iter().for_each(|line| {
@@ -121,44 +523,105 @@ fn build_monkey(lines: &[&str]) -> Monkey {
         }
     });
     Monkey {
-        items,
+        items: items.into_iter().map(T::from_u128).collect(),
         operation,
         test,
         true_throw,
         false_throw,
         inspected_items: 0,
+        true_throws: 0,
     }
 }
 
 fn parse_number(input: &str) -> u128 {
     input
         .split_whitespace()
-        .rev()
-        .next()
+        .next_back()
         .unwrap()
         .parse::<u128>()
         .unwrap()
 }
 
 fn parse_operation(input: &str) -> Operation {
-    let operation: Vec<&str> = input.split_whitespace().rev().take(2).collect();
+    let (_, expr) = input
+        .split_once('=')
+        .expect("operation should look like `new = <expr>`.");
+    let mut tokens = tokenize(expr).into_iter().peekable();
+    let operation = parse_sum(&mut tokens);
 
-    match operation[0].parse::<u128>() {
-        Ok(num) => Operation {
-            operator: String::from(operation[1]),
-            operand: Some(num),
-        },
-        Err(_) => Operation {
-            operator: String::from(operation[1]),
-            operand: None,
+    if let Some(token) = tokens.next() {
+        panic!("unexpected `{}` in operation: {}", token, input);
+    }
+    operation
+}
+
+/// Splits an expression into numbers, `old`, operators and parentheses.
+fn tokenize(expr: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = vec![];
+
+    expr.chars().for_each(|c| match c {
+        '+' | '-' | '*' | '/' | '(' | ')' => tokens.push(c.to_string()),
+        c if c.is_whitespace() => tokens.push(String::new()),
+        c => match tokens.last_mut() {
+            Some(last) if last.chars().all(|l| l.is_alphanumeric()) => last.push(c),
+            _ => tokens.push(c.to_string()),
         },
+    });
+    tokens.retain(|token| !token.is_empty());
+    tokens
+}
+
+type Tokens = Peekable<vec::IntoIter<String>>;
+
+// sum := product (('+' | '-') product)*
+fn parse_sum(tokens: &mut Tokens) -> Operation {
+    let mut left = parse_product(tokens);
+
+    while let Some(operator) = tokens.next_if(|t| t == "+" || t == "-") {
+        let right = parse_product(tokens);
+        left = Operation::Binary(Box::new(left), operator_char(&operator), Box::new(right));
+    }
+    left
+}
+
+// product := atom (('*' | '/') atom)*
+fn parse_product(tokens: &mut Tokens) -> Operation {
+    let mut left = parse_atom(tokens);
+
+    while let Some(operator) = tokens.next_if(|t| t == "*" || t == "/") {
+        let right = parse_atom(tokens);
+        left = Operation::Binary(Box::new(left), operator_char(&operator), Box::new(right));
+    }
+    left
+}
+
+// atom := 'old' | number | '(' sum ')'
+fn parse_atom(tokens: &mut Tokens) -> Operation {
+    let token = tokens.next().expect("operation ended early.");
+
+    match token.as_str() {
+        "old" => Operation::Old,
+        "(" => {
+            let inner = parse_sum(tokens);
+            match tokens.next().as_deref() {
+                Some(")") => inner,
+                _ => panic!("missing `)` in operation."),
+            }
+        }
+        number => Operation::Const(
+            number
+                .parse::<u128>()
+                .unwrap_or_else(|_| panic!("unexpected `{}` in operation.", number)),
+        ),
     }
 }
 
+fn operator_char(operator: &str) -> char {
+    operator.chars().next().unwrap()
+}
+
 fn parse_items(line: &str) -> Vec<u128> {
     line.split([':', ','].as_ref())
-        .map(|word| word.trim().parse::<u128>())
-        .filter(|item| item.is_ok())
-        .map(|num| num.unwrap())
+        .filter_map(|word| word.trim().parse::<u128>().ok())
         .collect()
 }
@@ -1,35 +1,135 @@
-use std::{fs, vec};
+use pathfinding::{dijkstra, dijkstra_distances, Path};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    env, fs,
+};
+
+type Pos = (usize, usize);
+
+/// How a hiker may move across the heightmap. Every step costs one, plus
+/// `effort` for each unit of height gained or lost.
+#[derive(Clone, Copy)]
+struct MoveRule {
+    max_climb: i32,
+    max_descent: i32,
+    diagonal: bool,
+    effort: u64,
+}
+
+impl MoveRule {
+    /// At most one step up, any step down.
+    const PUZZLE: MoveRule = MoveRule {
+        max_climb: 1,
+        max_descent: i32::MAX,
+        diagonal: false,
+        effort: 0,
+    };
+
+    fn can_step(&self, from: i32, to: i32) -> bool {
+        to - from <= self.max_climb && from - to <= self.max_descent
+    }
+
+    fn cost(&self, from: i32, to: i32) -> u64 {
+        1 + self.effort * from.abs_diff(to) as u64
+    }
+
+    /// The rule for walking a route backwards: climbs become descents.
+    fn reversed(&self) -> MoveRule {
+        MoveRule {
+            max_climb: self.max_descent,
+            max_descent: self.max_climb,
+            ..*self
+        }
+    }
+}
+
+struct Config {
+    rule: MoveRule,
+    color: bool,
+    ppm_prefix: Option<String>,
+}
+
+impl Config {
+    /// Usage: rust_sol [--max-climb <n>] [--max-descent <n>] [--diagonal]
+    ///                 [--effort <n>] [--color] [--ppm <prefix>]
+    ///
+    /// `--color` shades the maps by elevation, `--ppm` also saves each one as
+    /// `<prefix>1.ppm` and `<prefix>2.ppm`.
+    fn from_args() -> Config {
+        let mut config = Config {
+            rule: MoveRule::PUZZLE,
+            color: false,
+            ppm_prefix: None,
+        };
+        let mut args = env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .unwrap_or_else(|| panic!("{} needs a value.", arg))
+            };
+            let height = |value: String| {
+                value
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|&height| height >= 0)
+                    .unwrap_or_else(|| panic!("{} needs a non-negative number.", arg))
+            };
+
+            match arg.as_str() {
+                "--max-climb" => config.rule.max_climb = height(value()),
+                "--max-descent" => config.rule.max_descent = height(value()),
+                "--effort" => {
+                    config.rule.effort = value()
+                        .parse()
+                        .expect("--effort needs a non-negative number.")
+                }
+                "--diagonal" => config.rule.diagonal = true,
+                "--color" => config.color = true,
+                "--ppm" => config.ppm_prefix = Some(value()),
+                _ => panic!("Unknown argument: {}", arg),
+            }
+        }
+
+        config
+    }
+}
 
 fn main() {
     let contents = fs::read_to_string("input.txt").expect("Couldn't read file.");
+    let config = Config::from_args();
+    let rule = config.rule;
 
     let (grid, s_pos, e_pos) = build_grid(&contents);
-    let (path_len, prev) = bfs(&grid, s_pos, e_pos);
-    let (shortest_len, shortest_prev) = get_shortest_a_len(&grid, e_pos);
+    let path = shortest_path(&grid, s_pos, e_pos, &rule).expect("E can't be reached from S.");
+    let distances = DistancesToEnd::new(&grid, e_pos, &rule);
+    let shortest = get_shortest_a_path(&grid, &distances).expect("E can't be reached from any a.");
 
-    print_path(&grid, &prev, e_pos);
-    println!("Answer 1: {path_len}");
+    [&path, &shortest]
+        .iter()
+        .enumerate()
+        .for_each(|(part, route)| {
+            if config.color {
+                print!("{}", render_ansi(&grid, &route.nodes));
+            } else {
+                print_path(&grid, &route.nodes);
+            }
+            println!("Answer {}: {}", part + 1, route.cost);
 
-    print_path(&grid, &shortest_prev, e_pos);
-    println!("Answer 2: {shortest_len}");
+            if let Some(prefix) = &config.ppm_prefix {
+                let image = render_ppm(&grid, &route.nodes, 4);
+                fs::write(format!("{}{}.ppm", prefix, part + 1), image)
+                    .expect("Couldn't write image.");
+            }
+        });
 }
 
-fn print_path(
-    grid: &Vec<Vec<char>>,
-    prev: &Vec<Vec<Option<(usize, usize)>>>,
-    e_pos: (usize, usize),
-) {
-    let mut path = vec![];
-    let mut pos = e_pos;
-    path.push(e_pos);
-
-    while let Some(node) = prev[pos.0][pos.1] {
-        path.push(node);
-        pos = node;
-    }
+fn print_path(grid: &[Vec<char>], path: &[Pos]) {
+    let path: HashSet<&Pos> = path.iter().collect();
 
-    (0..grid.len()).into_iter().for_each(|i| {
-        (0..grid[i].len()).into_iter().for_each(|j| {
+    (0..grid.len()).for_each(|i| {
+        (0..grid[i].len()).for_each(|j| {
             if path.contains(&(i, j)) {
                 print!("#");
             } else {
@@ -41,101 +141,178 @@ fn print_path(
     println!();
 }
 
-fn get_shortest_a_len(
-    grid: &Vec<Vec<char>>,
-    e_pos: (usize, usize),
-) -> (u32, Vec<Vec<Option<(usize, usize)>>>) {
-    grid.iter()
-        .enumerate()
-        .map(|(i, line)| {
-            line.iter()
-                .enumerate()
-                .filter(|(_, ele)| **ele == 'a')
-                .map(|(j, _)| bfs(&grid, (i, j), e_pos))
-                .min()
-                .unwrap()
+/// The arrow leaving each cell of the route toward the next one. The last
+/// cell gets `E`.
+fn route_arrows(path: &[Pos]) -> HashMap<Pos, char> {
+    let mut arrows: HashMap<Pos, char> = path
+        .windows(2)
+        .map(|step| {
+            let (from, to) = (step[0], step[1]);
+            let arrow = match (to.0.cmp(&from.0), to.1.cmp(&from.1)) {
+                (Ordering::Less, Ordering::Equal) => '^',
+                (Ordering::Greater, Ordering::Equal) => 'v',
+                (Ordering::Equal, Ordering::Less) => '<',
+                (Ordering::Equal, Ordering::Greater) => '>',
+                (Ordering::Less, Ordering::Less) => '\u{2196}',
+                (Ordering::Less, Ordering::Greater) => '\u{2197}',
+                (Ordering::Greater, Ordering::Less) => '\u{2199}',
+                (Ordering::Greater, Ordering::Greater) => '\u{2198}',
+                (Ordering::Equal, Ordering::Equal) => '*',
+            };
+            (from, arrow)
         })
-        .min()
-        .unwrap()
-}
-
-fn bfs(
-    grid: &Vec<Vec<char>>,
-    s_pos: (usize, usize),
-    e_pos: (usize, usize),
-) -> (u32, Vec<Vec<Option<(usize, usize)>>>) {
-    let mut visited = vec![vec![false; grid[0].len()]; grid.len()];
-    let mut queue = vec![];
-    let mut dist = vec![vec![u32::MAX; grid[0].len()]; grid.len()];
-    let mut prev: Vec<Vec<Option<(usize, usize)>>> = vec![vec![None; grid[0].len()]; grid.len()];
-    let mut done = false;
-
-    // distance to start is zero, and start has been visited.
-    dist[s_pos.0][s_pos.1] = 0;
-    visited[s_pos.0][s_pos.1] = true;
-    queue.push(s_pos);
-
-    while !queue.is_empty() && !done {
-        let curr = queue.remove(0);
-
-        let adj = get_adj_nodes(curr, &grid);
-
-        adj.iter().for_each(|node| {
-            if !visited[node.0][node.1] {
-                visited[node.0][node.1] = true;
-                dist[node.0][node.1] = dist[curr.0][curr.1] + 1;
-                prev[node.0][node.1] = Some(curr);
-                queue.push(*node);
-
-                // stop when we reach the end.
-                if *node == e_pos {
-                    done = true;
-                    return;
-                }
+        .collect();
+
+    if let Some(&end) = path.last() {
+        arrows.insert(end, 'E');
+    }
+    arrows
+}
+
+/// Elevation `a` to `z` as 0.0 to 1.0.
+fn elevation(height: char) -> f64 {
+    (height as u8 - b'a') as f64 / 25.0
+}
+
+/// Shades every cell by elevation on the 24 step grey ramp of the 256 colour
+/// palette and draws the route over it with arrows.
+fn render_ansi(grid: &[Vec<char>], path: &[Pos]) -> String {
+    let arrows = route_arrows(path);
+    let mut out = String::new();
+
+    grid.iter().enumerate().for_each(|(i, row)| {
+        row.iter().enumerate().for_each(|(j, &height)| {
+            let shade = 232 + (elevation(height) * 23.0).round() as u8;
+            let (fg, symbol) = match arrows.get(&(i, j)) {
+                Some(&arrow) => ("\x1B[1;31m", arrow),
+                None => ("\x1B[38;5;244m", height),
+            };
+            out += &format!("\x1B[48;5;{}m{}{}", shade, fg, symbol);
+        });
+        out += "\x1B[0m\n";
+    });
+
+    out + "\n"
+}
+
+/// A binary PPM with each cell `scale` pixels wide, shaded from dark green in
+/// the valleys to white on the peaks with the route in red.
+fn render_ppm(grid: &[Vec<char>], path: &[Pos], scale: usize) -> Vec<u8> {
+    let route: HashSet<&Pos> = path.iter().collect();
+    let (width, height) = (grid[0].len() * scale, grid.len() * scale);
+    let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+
+    (0..height).for_each(|y| {
+        (0..width).for_each(|x| {
+            let cell = (y / scale, x / scale);
+
+            if route.contains(&cell) {
+                image.extend_from_slice(&[220, 50, 47]);
+            } else {
+                let t = elevation(grid[cell.0][cell.1]);
+                let lerp = |low: f64, high: f64| (low + (high - low) * t) as u8;
+                image.extend_from_slice(&[lerp(34.0, 240.0), lerp(85.0, 240.0), lerp(34.0, 240.0)]);
             }
         });
-    }
-    (dist[e_pos.0][e_pos.1], prev)
+    });
+
+    image
 }
 
-fn get_adj_nodes(curr: (usize, usize), grid: &Vec<Vec<char>>) -> Vec<(usize, usize)> {
-    let mut adj = vec![];
+/// Costs to E from every cell that can reach it, found with a single search
+/// backwards from E.
+struct DistancesToEnd {
+    dist: HashMap<Pos, u64>,
+    rule: MoveRule,
+}
 
-    let node = grid[curr.0][curr.1] as i32;
+impl DistancesToEnd {
+    fn new(grid: &[Vec<char>], e_pos: Pos, rule: &MoveRule) -> Self {
+        let reversed = rule.reversed();
+        let neighbours = |curr: &Pos| get_adj_nodes(*curr, grid, &reversed);
 
-    // up: curr.0 - 1, curr.1
-    if curr.0 > 0 {
-        let up = grid[curr.0 - 1][curr.1] as i32;
-        if up - node <= 1 {
-            adj.push((curr.0 - 1, curr.1));
+        DistancesToEnd {
+            dist: dijkstra_distances(&neighbours, [e_pos]),
+            rule: *rule,
         }
     }
-    // down: curr.0 + 1, curr.1
-    if curr.0 < grid.len() - 1 {
-        let down = grid[curr.0 + 1][curr.1] as i32;
-        if down - node <= 1 {
-            adj.push((curr.0 + 1, curr.1));
-        }
+
+    fn from(&self, pos: Pos) -> Option<u64> {
+        self.dist.get(&pos).copied()
     }
-    // left: curr.0, curr.1 + 1
-    if curr.1 < grid[0].len() - 1 {
-        let left = grid[curr.0][curr.1 + 1] as i32;
-        if left - node <= 1 {
-            adj.push((curr.0, curr.1 + 1));
-        }
-    };
-    // right: curr.0, curr.1 - 1
-    if curr.1 > 0 {
-        let right = grid[curr.0][curr.1 - 1] as i32;
-        if right - node <= 1 {
-            adj.push((curr.0, curr.1 - 1));
+
+    /// A cheapest route from `pos` to E, following the costs downhill.
+    fn path_from(&self, grid: &[Vec<char>], pos: Pos) -> Option<Path<Pos>> {
+        let cost = self.from(pos)?;
+        let mut nodes = vec![pos];
+        let mut remaining = cost;
+
+        while remaining > 0 {
+            let curr = *nodes.last().unwrap();
+            let (next, step) = get_adj_nodes(curr, grid, &self.rule)
+                .into_iter()
+                .find(|&(node, step)| {
+                    remaining
+                        .checked_sub(step)
+                        .is_some_and(|rest| self.from(node) == Some(rest))
+                })
+                .unwrap();
+
+            nodes.push(next);
+            remaining -= step;
         }
-    };
 
-    adj
+        Some(Path { cost, nodes })
+    }
+}
+
+/// Unreachable starts are skipped rather than counted as a very long path.
+fn get_shortest_a_path(grid: &[Vec<char>], distances: &DistancesToEnd) -> Option<Path<Pos>> {
+    let start = grid
+        .iter()
+        .enumerate()
+        .flat_map(|(i, line)| {
+            line.iter()
+                .enumerate()
+                .filter(|(_, ele)| **ele == 'a')
+                .map(move |(j, _)| (i, j))
+        })
+        .filter(|&pos| distances.from(pos).is_some())
+        .min_by_key(|&pos| distances.from(pos))?;
+
+    distances.path_from(grid, start)
+}
+
+fn shortest_path(grid: &[Vec<char>], s_pos: Pos, e_pos: Pos, rule: &MoveRule) -> Option<Path<Pos>> {
+    let neighbours = |curr: &Pos| get_adj_nodes(*curr, grid, rule);
+
+    dijkstra(&neighbours, s_pos, |node| *node == e_pos)
+}
+
+/// Cells reachable in one step from `curr`, with the cost of each step.
+fn get_adj_nodes(curr: Pos, grid: &[Vec<char>], rule: &MoveRule) -> Vec<(Pos, u64)> {
+    let straight = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+    let diagonal = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+    let node = grid[curr.0][curr.1] as i32;
+
+    straight
+        .iter()
+        .chain(diagonal.iter().filter(|_| rule.diagonal))
+        .filter_map(|&(di, dj)| {
+            let i = curr.0.checked_add_signed(di).filter(|&i| i < grid.len())?;
+            let j = curr
+                .1
+                .checked_add_signed(dj)
+                .filter(|&j| j < grid[i].len())?;
+            let next = grid[i][j] as i32;
+
+            rule.can_step(node, next)
+                .then(|| ((i, j), rule.cost(node, next)))
+        })
+        .collect()
 }
 
-fn build_grid(contents: &String) -> (Vec<Vec<char>>, (usize, usize), (usize, usize)) {
+fn build_grid(contents: &str) -> (Vec<Vec<char>>, Pos, Pos) {
     let mut grid = vec![];
     let mut s_pos = (0, 0);
     let mut e_pos = (0, 0);
@@ -1,115 +1,260 @@
-use std::{cmp::Ordering, fs};
+use std::{cmp::Ordering, env, fmt, fs, str::FromStr};
 
-fn main() {
-    let contents = fs::read_to_string("input.txt").expect("Couldn't read file.");
-    let mut filtered_list: Vec<&str> = contents.lines().filter(|line| !line.is_empty()).collect();
-
-    let (div_1, div_2) = ("[[2]]", "[[6]]");
-    filtered_list.push(div_1);
-    filtered_list.push(div_2);
-    filtered_list.sort_by(|l1, l2| get_order(l1, l2));
+/// Equality follows the puzzle's order too, so `1 == [1]`.
+#[derive(Debug, Clone)]
+enum Packet {
+    Int(u64),
+    List(Vec<Packet>),
+}
 
-    let line_num1 = filtered_list.iter().position(|&line| line == div_1).unwrap() + 1;
-    let line_num2 = filtered_list.iter().position(|&line| line == div_2).unwrap() + 1;
+impl Ord for Packet {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Packet::Int(left), Packet::Int(right)) => left.cmp(right),
+            (Packet::List(left), Packet::List(right)) => left.cmp(right),
+            // mixed types compare as if the integer were a list of one
+            (Packet::Int(_), Packet::List(right)) => std::slice::from_ref(self).cmp(right),
+            (Packet::List(left), Packet::Int(_)) => {
+                left.as_slice().cmp(std::slice::from_ref(other))
+            }
+        }
+    }
+}
 
-    println!("Answer 1: {}", get_inorder_count(&contents));
-    println!("Answer 2: {}", line_num1 * line_num2);
+impl PartialOrd for Packet {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
-fn get_order(l1: &str, l2: &str) -> Ordering {
-    let ord = are_in_order(l1, l2);
-    match ord {
-        Some(true) => Ordering::Less,
-        Some(false) => Ordering::Greater,
-        None => Ordering::Equal,
+impl PartialEq for Packet {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
     }
 }
 
-fn get_inorder_count(contents: &String) -> usize {
-    let iter = contents.lines().filter(|line| !line.is_empty());
-    let mut iter2 = contents.lines().filter(|line| !line.is_empty());
-    iter2.next();
+impl Eq for Packet {}
 
-    iter.step_by(2)
-        .zip(iter2.step_by(2))
-        .enumerate()
-        .filter(|(_, (l1, l2))| are_in_order(l1, l2).unwrap_or(true))
-        .map(|(index, _)| index + 1)
-        .sum()
+impl Packet {
+    /// Compares like `cmp`, writing each step in the same words the puzzle uses
+    /// to walk through its examples.
+    fn explain_cmp(&self, other: &Self, depth: usize, trace: &mut Vec<String>) -> Ordering {
+        let indent = "  ".repeat(depth);
+        let note = |trace: &mut Vec<String>, text: &str| {
+            trace.push(format!("{}  - {}", indent, text));
+        };
+        trace.push(format!("{}- Compare {} vs {}", indent, self, other));
+
+        match (self, other) {
+            (Packet::Int(left), Packet::Int(right)) => {
+                let order = left.cmp(right);
+                match order {
+                    Ordering::Less => note(
+                        trace,
+                        "Left side is smaller, so inputs are in the right order",
+                    ),
+                    Ordering::Greater => note(
+                        trace,
+                        "Right side is smaller, so inputs are not in the right order",
+                    ),
+                    Ordering::Equal => (),
+                }
+                order
+            }
+            (Packet::List(left), Packet::List(right)) => {
+                for (l, r) in left.iter().zip(right) {
+                    let order = l.explain_cmp(r, depth + 1, trace);
+                    if order != Ordering::Equal {
+                        return order;
+                    }
+                }
+
+                let order = left.len().cmp(&right.len());
+                match order {
+                    Ordering::Less => note(
+                        trace,
+                        "Left side ran out of items, so inputs are in the right order",
+                    ),
+                    Ordering::Greater => note(
+                        trace,
+                        "Right side ran out of items, so inputs are not in the right order",
+                    ),
+                    Ordering::Equal => (),
+                }
+                order
+            }
+            (Packet::Int(_), Packet::List(_)) => {
+                let left = Packet::List(vec![self.clone()]);
+                note(
+                    trace,
+                    &format!("Mixed types; convert left to {} and retry comparison", left),
+                );
+                left.explain_cmp(other, depth + 1, trace)
+            }
+            (Packet::List(_), Packet::Int(_)) => {
+                let right = Packet::List(vec![other.clone()]);
+                note(
+                    trace,
+                    &format!(
+                        "Mixed types; convert right to {} and retry comparison",
+                        right
+                    ),
+                );
+                self.explain_cmp(&right, depth + 1, trace)
+            }
+        }
+    }
 }
 
-fn are_in_order(l1: &str, l2: &str) -> Option<bool> {
-    if l1.starts_with("[") && l2.starts_with("[") {
-        return are_lists_in_order(l1, l2);
-    } else if l1.starts_with("[") {
-        return are_lists_in_order(l1, &to_list(l2)[..]);
-    } else if l2.starts_with("[") {
-        return are_lists_in_order(&to_list(l1)[..], l2);
-    } else {
-        return are_numbers_in_order(l1, l2);
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Packet::Int(num) => write!(f, "{}", num),
+            Packet::List(items) => {
+                write!(f, "[")?;
+                items.iter().enumerate().try_for_each(|(index, item)| {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)
+                })?;
+                write!(f, "]")
+            }
+        }
     }
 }
 
-fn are_numbers_in_order(l1: &str, l2: &str) -> Option<bool> {
-    let num1 = l1.parse::<i32>().unwrap();
-    let num2 = l2.parse::<i32>().unwrap();
+impl FromStr for Packet {
+    type Err = String;
 
-    if num1 == num2 {
-        return None;
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            input: line.trim().as_bytes(),
+            pos: 0,
+        };
+        let packet = parser.parse_packet()?;
+
+        match parser.peek() {
+            None => Ok(packet),
+            Some(c) => Err(format!(
+                "unexpected `{}` at {} in {}",
+                c as char, parser.pos, line
+            )),
+        }
     }
-    Some(num1 < num2)
 }
 
-fn to_list(l2: &str) -> String {
-    let mut result = String::from("[");
-    result.push_str(l2);
-    result.push_str("]");
-    result
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
 }
 
-fn are_lists_in_order(list1: &str, list2: &str) -> Option<bool> {
-    let list1_items = parse_list(list1);
-    let list2_items = parse_list(list2);
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    // packet := int | '[' (packet (',' packet)*)? ']'
+    fn parse_packet(&mut self) -> Result<Packet, String> {
+        match self.peek() {
+            Some(b'[') => self.parse_list(),
+            Some(c) if c.is_ascii_digit() => self.parse_int(),
+            Some(c) => Err(format!("unexpected `{}` at {}", c as char, self.pos)),
+            None => Err(String::from("packet ended early")),
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<Packet, String> {
+        let mut items = vec![];
+        self.pos += 1;
 
-    for index in 0..list1_items.len() {
-        if index >= list2_items.len() {
-            return Some(false);
-        } else if let Some(in_order) = are_in_order(list1_items[index], list2_items[index]) {
-            return Some(in_order);
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Packet::List(items));
+        }
+
+        loop {
+            items.push(self.parse_packet()?);
+
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Packet::List(items));
+                }
+                Some(c) => return Err(format!("unexpected `{}` at {}", c as char, self.pos)),
+                None => return Err(String::from("missing `]`")),
+            }
         }
     }
 
-    if list1_items.len() < list2_items.len() {
-        return Some(true);
+    fn parse_int(&mut self) -> Result<Packet, String> {
+        let start = self.pos;
+
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+
+        std::str::from_utf8(&self.input[start..self.pos])
+            .unwrap()
+            .parse()
+            .map(Packet::Int)
+            .map_err(|err| format!("bad integer at {}: {}", start, err))
     }
-    None
 }
 
-fn parse_list(list: &str) -> Vec<&str> {
-    let mut items = vec![];
-    let striped_list = &list[1..list.len() - 1];
+/// Usage: rust_sol [--explain <pair number>]
+fn main() {
+    let contents = fs::read_to_string("input.txt").expect("Couldn't read file.");
+    let packets = parse_packets(&contents);
 
-    if striped_list.is_empty() {
-        return items;
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let [flag, pair] = args.as_slice() {
+        assert!(flag == "--explain", "Unknown argument: {}", flag);
+        let pair = pair.parse().expect("Couldn't parse pair number.");
+        print!("{}", explain_pair(&packets, pair));
+        return;
     }
 
-    let mut start_index = 0;
-    let mut open_brackets = 0;
+    println!("Answer 1: {}", get_inorder_count(&packets));
 
-    striped_list
-        .chars()
-        .enumerate()
-        .for_each(|(index, char)| match char {
-            '[' => open_brackets += 1,
+    // where the dividers land without sorting: after every packet ordered
+    // below or equal to them (as if they were appended and stably sorted), and
+    // [[6]] after [[2]] as well
+    let (div_1, div_2): (Packet, Packet) = ("[[2]]".parse().unwrap(), "[[6]]".parse().unwrap());
+    let line_num1 = 1 + packets.iter().filter(|&packet| *packet <= div_1).count();
+    let line_num2 = 2 + packets.iter().filter(|&packet| *packet <= div_2).count();
 
-            ']' => open_brackets -= 1,
+    println!("Answer 2: {}", line_num1 * line_num2);
+}
 
-            ',' if open_brackets == 0 => {
-                items.push(&striped_list[start_index..index]);
-                start_index = index + 1;
-            }
-            _ => (),
-        });
-    items.push(&striped_list[start_index..]);
-    items
+fn parse_packets(contents: &str) -> Vec<Packet> {
+    contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.parse().expect("Couldn't parse packet."))
+        .collect()
+}
+
+/// The comparison trace for a pair, numbered from 1 like the puzzle.
+fn explain_pair(packets: &[Packet], pair: usize) -> String {
+    let (left, right) = match packets.chunks(2).nth(pair.wrapping_sub(1)) {
+        Some([left, right]) => (left, right),
+        _ => panic!("There's no pair {}.", pair),
+    };
+
+    let mut trace = vec![format!("== Pair {} ==", pair)];
+    let order = left.explain_cmp(right, 0, &mut trace);
+    assert_eq!(order, left.cmp(right), "The trace disagrees with Ord.");
+
+    trace.join("\n") + "\n"
+}
+
+fn get_inorder_count(packets: &[Packet]) -> usize {
+    packets
+        .chunks(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[0] <= pair[1])
+        .map(|(index, _)| index + 1)
+        .sum()
 }
@@ -1,16 +1,71 @@
-use std::{cmp, fs};
+use std::{
+    cmp,
+    collections::{HashMap, HashSet},
+    env, fs,
+    io::{self, Write},
+    iter,
+    path::PathBuf,
+    thread,
+    time::Duration,
+};
 
+/// The cave is stored sparsely, so rock can be anywhere. Anything missing is
+/// air, and once the floor is added it's rock across the whole row.
 struct Grid {
-    map: Vec<Vec<Material>>,
-    floor: usize,
+    map: HashMap<Point, Material>,
+    lowest_rock: i32,
+    min_x: i32,
+    max_x: i32,
+    floor: Option<i32>,
+    sources: Vec<Source>,
+}
+
+/// Somewhere grains are dropped from. Each grain tries `moves` in order and
+/// takes the first one into air, resting when none are left. `path` is the
+/// fall path of the last grain, so the next one can pick up from the last
+/// spot along it that's still free.
+#[derive(Clone)]
+struct Source {
+    point: Point,
+    material: Material,
+    moves: Vec<(i32, i32)>,
+    path: Vec<Point>,
+}
+
+impl Source {
+    fn new(point: Point, material: Material) -> Source {
+        Source {
+            point,
+            moves: material.default_moves(),
+            material,
+            path: vec![],
+        }
+    }
+}
+
+enum Drop {
+    Rested,
+    Spilled,
+    Blocked,
 }
 
 impl Grid {
-    fn new() -> Grid {
+    fn new(sources: Vec<Source>) -> Grid {
         Grid {
-            map: vec![vec![Material::Air; 650]; 1000],
-            floor: 0,
+            map: HashMap::new(),
+            lowest_rock: 0,
+            min_x: sources.iter().map(|s| s.point.x).min().unwrap(),
+            max_x: sources.iter().map(|s| s.point.x).max().unwrap(),
+            floor: None,
+            sources,
+        }
+    }
+
+    fn get(&self, point: &Point) -> Material {
+        if self.floor == Some(point.y) {
+            return Material::Rock;
         }
+        self.map.get(point).cloned().unwrap_or(Material::Air)
     }
 
     fn add_wall(&mut self, start: &Point, end: &Point) {
@@ -18,16 +73,21 @@ impl Grid {
             let min = cmp::min(start.y, end.y);
             let max = cmp::max(start.y, end.y);
 
-            (min..=max).for_each(|y| self.map[start.x][y] = Material::Rock);
+            (min..=max).for_each(|y| {
+                self.map.insert(Point::new(start.x, y), Material::Rock);
+            });
         } else if start.y == end.y {
             let min = cmp::min(start.x, end.x);
             let max = cmp::max(start.x, end.x);
 
-            (min..=max).for_each(|x| self.map[x][start.y] = Material::Rock);
+            (min..=max).for_each(|x| {
+                self.map.insert(Point::new(x, start.y), Material::Rock);
+            });
         }
 
-        let new_floor = cmp::max(start.y, end.y) + 2;
-        self.floor = cmp::max(new_floor, self.floor);
+        self.lowest_rock = cmp::max(self.lowest_rock, cmp::max(start.y, end.y));
+        self.min_x = cmp::min(self.min_x, cmp::min(start.x, end.x));
+        self.max_x = cmp::max(self.max_x, cmp::max(start.x, end.x));
     }
 
     fn add_path(&mut self, path: &[Point]) {
@@ -37,107 +97,442 @@ impl Grid {
     }
 
     fn add_floor(&mut self) {
-        let start = Point::new(0, self.floor);
-        let end = Point::new(self.map.len() - 1, self.floor);
-        self.add_wall(&start, &end);
-    }
-
-    fn display_grid(&self, min: Point, max: Point) {
-        (min.y..=max.y).into_iter().for_each(|y| {
-            print!("{y}\t");
-            (min.x..=max.x)
-                .into_iter()
-                .for_each(|x| match self.map[x][y] {
-                    Material::Air => print!("."),
-                    Material::Rock => print!("#"),
-                    Material::Sand => print!("o"),
-                });
-            println!();
+        self.floor = Some(self.lowest_rock + 2);
+    }
+
+    fn bottom(&self) -> i32 {
+        self.floor.unwrap_or(self.lowest_rock)
+    }
+
+    /// Without a floor, anything below the lowest rock falls forever. Nothing
+    /// piles up further out to the side than the cave is deep, so anything
+    /// flowing past that is gone too (water would spread along the floor
+    /// forever otherwise).
+    fn spilled(&self, point: &Point) -> bool {
+        let spread = self.bottom() + 1;
+        point.y > self.bottom() || point.x < self.min_x - spread || point.x > self.max_x + spread
+    }
+
+    /// The smallest box holding every rock, grain and source, with a column of
+    /// air either side. Once there's a floor it's included too.
+    fn bounds(&self) -> (Point, Point) {
+        let mut min = self.sources[0].point;
+        let mut max = min;
+
+        self.map
+            .keys()
+            .chain(self.sources.iter().map(|source| &source.point))
+            .for_each(|point| {
+                min = Point::new(cmp::min(min.x, point.x), cmp::min(min.y, point.y));
+                max = Point::new(cmp::max(max.x, point.x), cmp::max(max.y, point.y));
+            });
+
+        if let Some(floor) = self.floor {
+            max.y = cmp::max(max.y, floor);
+        }
+        (Point::new(min.x - 1, min.y), Point::new(max.x + 1, max.y))
+    }
+
+    fn render(&self, min: Point, max: Point) -> String {
+        (min.y..=max.y)
+            .map(|y| {
+                let row: String = (min.x..=max.x)
+                    .map(|x| self.get(&Point::new(x, y)).symbol())
+                    .collect();
+                row + "\n"
+            })
+            .collect()
+    }
+
+    /// Same window as `render`, each cell drawn as a `scale` pixel square.
+    fn to_ppm(&self, min: Point, max: Point, scale: usize) -> Vec<u8> {
+        let width = (max.x - min.x + 1) as usize * scale;
+        let height = (max.y - min.y + 1) as usize * scale;
+        let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+
+        (min.y..=max.y).for_each(|y| {
+            let row: Vec<u8> = (min.x..=max.x)
+                .flat_map(|x| iter::repeat_n(self.get(&Point::new(x, y)).color(), scale))
+                .flatten()
+                .collect();
+            (0..scale).for_each(|_| image.extend_from_slice(&row));
         });
+
+        image
     }
 
-    fn drop_sand(&mut self, drop_point: &Point) -> bool {
-        let mut sand_location = Point::new(drop_point.x, drop_point.y);
-
-        if self.map[sand_location.x][sand_location.y] == Material::Sand {
-            return false;
-        }
-
-        while sand_location.y <= self.floor {
-            // try to go down
-            if self.map[sand_location.x][sand_location.y + 1] == Material::Air {
-                sand_location.y += 1;
-            // try to go down and to the left
-            } else if self.map[sand_location.x - 1][sand_location.y + 1] == Material::Air {
-                sand_location.y += 1;
-                sand_location.x -= 1;
-            // try to go down and to the right
-            } else if self.map[sand_location.x + 1][sand_location.y + 1] == Material::Air {
-                sand_location.y += 1;
-                sand_location.x += 1;
-            } else {
-                self.map[sand_location.x][sand_location.y] = Material::Sand;
-                return true;
+    fn display_grid(&self) {
+        let (min, max) = self.bounds();
+
+        self.render(min, max)
+            .lines()
+            .zip(min.y..)
+            .for_each(|(row, y)| println!("{y}\t{row}"));
+    }
+
+    fn drop_grain(&mut self, source: usize) -> Drop {
+        let mut path = std::mem::take(&mut self.sources[source].path);
+        let (start, material) = (self.sources[source].point, self.sources[source].material);
+        let moves = self.sources[source].moves.clone();
+
+        // grains from other sources may have landed on this path since
+        if let Some(filled) = path.iter().position(|p| self.get(p) != Material::Air) {
+            path.truncate(filled);
+        }
+        if path.is_empty() {
+            if self.get(&start) != Material::Air {
+                return Drop::Blocked;
             }
+            path.push(start);
         }
-        false
+
+        // a grain never goes back somewhere it's already been, so sideways
+        // moves can't loop
+        let mut on_path: HashSet<Point> = path.iter().copied().collect();
+
+        let drop = loop {
+            let grain = *path.last().unwrap();
+            if self.spilled(&grain) {
+                break Drop::Spilled;
+            }
+
+            let next = moves
+                .iter()
+                .map(|&(dx, dy)| Point::new(grain.x + dx, grain.y + dy))
+                .find(|next| self.get(next) == Material::Air && !on_path.contains(next));
+
+            match next {
+                Some(next) => {
+                    path.push(next);
+                    on_path.insert(next);
+                }
+                None => {
+                    self.map.insert(grain, material);
+                    path.pop();
+                    break Drop::Rested;
+                }
+            }
+        };
+
+        self.sources[source].path = path;
+        drop
     }
 
     fn count_sand_drops(&mut self) -> u32 {
-        let drop_point = Point::new(500, 0);
+        self.fill(|_| ())
+    }
+
+    /// Takes turns dropping a grain from each source until every source has
+    /// either spilled a grain or been buried, returning how many came to rest.
+    /// `on_grain` sees the grid each time one does.
+    fn fill(&mut self, mut on_grain: impl FnMut(&Grid)) -> u32 {
+        let mut active: Vec<usize> = (0..self.sources.len()).collect();
+        let mut count = 0;
+
+        while !active.is_empty() {
+            active.retain(|&source| match self.drop_grain(source) {
+                Drop::Rested => {
+                    count += 1;
+                    on_grain(self);
+                    true
+                }
+                Drop::Spilled | Drop::Blocked => false,
+            });
+        }
+        count
+    }
+
+    /// Counts how much sand ends up resting once there's a floor, without
+    /// dropping any. Sand fills every cell reachable from the source through
+    /// the three cells above it, so this works row by row down to the floor.
+    /// Only valid for a single source with the standard sand moves.
+    fn count_filled_triangle(&self) -> u32 {
+        let source = self.sources[0].point;
+        let floor = self.lowest_rock + 2;
+        let mut row = HashSet::from([source.x]);
         let mut count = 0;
-        while self.drop_sand(&drop_point) {
-            count += 1;
+
+        for y in source.y..floor {
+            row.retain(|&x| self.get(&Point::new(x, y)) != Material::Rock);
+            count += row.len() as u32;
+
+            row = row.iter().flat_map(|&x| [x - 1, x, x + 1]).collect();
         }
         count
     }
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 enum Material {
     Air,
     Rock,
     Sand,
+    Water,
+}
+
+impl Material {
+    fn parse(name: &str) -> Material {
+        match name {
+            "sand" => Material::Sand,
+            "water" => Material::Water,
+            _ => panic!("Unknown material: {}", name),
+        }
+    }
+
+    /// Sand tries down, then down-left, then down-right. Water does the same
+    /// but also spreads sideways when it can't fall.
+    fn default_moves(&self) -> Vec<(i32, i32)> {
+        match self {
+            Material::Water => vec![(0, 1), (-1, 1), (1, 1), (-1, 0), (1, 0)],
+            _ => vec![(0, 1), (-1, 1), (1, 1)],
+        }
+    }
+
+    fn color(&self) -> [u8; 3] {
+        match self {
+            Material::Air => [0, 0, 0],
+            Material::Rock => [88, 88, 88],
+            Material::Sand => [230, 190, 100],
+            Material::Water => [38, 139, 210],
+        }
+    }
+
+    fn symbol(&self) -> char {
+        match self {
+            Material::Air => '.',
+            Material::Rock => '#',
+            Material::Sand => 'o',
+            Material::Water => '~',
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Point {
-    x: usize,
-    y: usize,
+    x: i32,
+    y: i32,
 }
 
 impl Point {
-    fn new(x: usize, y: usize) -> Point {
+    fn new(x: i32, y: i32) -> Point {
         Point { x, y }
     }
 }
 
+enum Animation {
+    Terminal(Duration),
+    Frames(PathBuf),
+}
+
+struct Config {
+    triangle: bool,
+    sources: Vec<Source>,
+    animation: Option<Animation>,
+    every: usize,
+}
+
+impl Config {
+    /// Usage: rust_sol [--triangle] [--source <x>,<y>[,<material>]]...
+    ///                 [--moves <material>=<dx>,<dy>;<dx>,<dy>...]...
+    ///                 [--animate <delay ms> | --frames <dir>] [--every <grains>]
+    ///
+    /// `--triangle` solves part 2 row by row instead of dropping every grain,
+    /// so it can't be combined with other sources or moves.
+    /// `--source` replaces the default sand source at 500,0 and can be given
+    /// more than once; `--moves` sets the fall directions for a material, in
+    /// the order they're tried (none of them can go up). Animations show both
+    /// parts, one frame every `--every` grains (1 by default).
+    fn from_args() -> Config {
+        let mut triangle = false;
+        let mut animation = None;
+        let mut every = 1;
+        let mut sources = vec![];
+        let mut moves = HashMap::new();
+        let mut args = env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--triangle" => triangle = true,
+                "--source" => {
+                    let source = args.next().expect("--source needs a point.");
+                    let mut split = source.split(',');
+                    let x = split.next().unwrap().parse().expect("Bad source x.");
+                    let y = split
+                        .next()
+                        .expect("Bad source.")
+                        .parse()
+                        .expect("Bad source y.");
+                    let material = split.next().map_or(Material::Sand, Material::parse);
+
+                    sources.push(Source::new(Point::new(x, y), material));
+                }
+                "--moves" => {
+                    let rule = args.next().expect("--moves needs a rule.");
+                    let (material, offsets) = rule.split_once('=').expect("Bad move rule.");
+                    let offsets: Vec<(i32, i32)> = offsets
+                        .split(';')
+                        .map(|offset| {
+                            let (dx, dy) = offset.split_once(',').expect("Bad move.");
+                            (
+                                dx.parse().expect("Bad move."),
+                                dy.parse().expect("Bad move."),
+                            )
+                        })
+                        .collect();
+                    assert!(
+                        offsets.iter().all(|&(_, dy)| dy >= 0),
+                        "Moves can't go up, or grains would climb forever."
+                    );
+
+                    moves.insert(material.to_string(), offsets);
+                }
+                "--animate" => {
+                    let delay = args
+                        .next()
+                        .and_then(|delay| delay.parse().ok())
+                        .expect("--animate needs a delay in milliseconds.");
+                    animation = Some(Animation::Terminal(Duration::from_millis(delay)));
+                }
+                "--frames" => {
+                    let dir = args.next().expect("--frames needs an output directory.");
+                    animation = Some(Animation::Frames(PathBuf::from(dir)));
+                }
+                "--every" => {
+                    every = args
+                        .next()
+                        .and_then(|every| every.parse().ok())
+                        .filter(|&every| every > 0)
+                        .expect("--every needs a number of grains.");
+                }
+                _ => panic!("Unknown argument: {}", arg),
+            }
+        }
+
+        if sources.is_empty() {
+            sources.push(Source::new(Point::new(500, 0), Material::Sand));
+        }
+        assert!(
+            !triangle
+                || (sources.len() == 1
+                    && sources[0].material == Material::Sand
+                    && moves.is_empty()),
+            "--triangle only works for a single sand source with the standard moves."
+        );
+        sources.iter_mut().for_each(|source| {
+            let name = match source.material {
+                Material::Water => "water",
+                _ => "sand",
+            };
+            if let Some(offsets) = moves.get(name) {
+                source.moves = offsets.clone();
+            }
+        });
+
+        Config {
+            triangle,
+            sources,
+            animation,
+            every,
+        }
+    }
+}
+
 fn main() {
     let contents = fs::read_to_string("input.txt").expect("Couldn't read file.");
+    let config = Config::from_args();
 
-    let mut grid = Grid::new();
-    fill_grid(contents, &mut grid);
+    if let Some(animation) = &config.animation {
+        animate(&contents, &config, animation).expect("Couldn't write animation.");
+        return;
+    }
+
+    let mut grid = Grid::new(config.sources);
+    fill_grid(&contents, &mut grid);
 
     // part 1
     let mut count = grid.count_sand_drops();
 
     // uncomment out line bellow to show map
-    grid.display_grid(Point::new(400, 0), Point::new(550, grid.floor));
+    grid.display_grid();
     println!("Answer 1: {count}");
 
     // part 2 (just add to count from part 1, so we don't waste work)
+    if config.triangle {
+        count = grid.count_filled_triangle();
+        println!("Answer 2: {count}");
+        return;
+    }
     grid.add_floor();
     count += grid.count_sand_drops();
 
     // uncomment out line bellow to show map
-    grid.display_grid(Point::new(400, 0), Point::new(550, grid.floor));
+    grid.display_grid();
     println!("Answer 2: {count}");
 }
 
-fn fill_grid(contents: String, grid: &mut Grid) {
+fn animate(contents: &str, config: &Config, animation: &Animation) -> io::Result<()> {
+    // run it once first to see how big the pile gets, so every frame is the
+    // same size
+    let mut grid = Grid::new(config.sources.clone());
+    fill_grid(contents, &mut grid);
+    grid.count_sand_drops();
+    grid.add_floor();
+    grid.count_sand_drops();
+    let window = grid.bounds();
+
+    if let Animation::Frames(dir) = animation {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut grid = Grid::new(config.sources.clone());
+    fill_grid(contents, &mut grid);
+    let (mut grains, mut frame) = (0, 0);
+    let mut result = show_frame(&grid, window, frame, animation);
+
+    let mut on_grain = |grid: &Grid| {
+        grains += 1;
+        if result.is_ok() && grains % config.every == 0 {
+            frame += 1;
+            result = show_frame(grid, window, frame, animation);
+        }
+    };
+    grid.fill(&mut on_grain);
+    grid.add_floor();
+    grid.fill(&mut on_grain);
+    result?;
+
+    // always finish on the settled pile
+    if grains % config.every != 0 {
+        show_frame(&grid, window, frame + 1, animation)?;
+    }
+    Ok(())
+}
+
+fn show_frame(
+    grid: &Grid,
+    (min, max): (Point, Point),
+    frame: usize,
+    animation: &Animation,
+) -> io::Result<()> {
+    match animation {
+        Animation::Terminal(delay) => {
+            print!("\x1B[2J\x1B[H{}", grid.render(min, max));
+            io::stdout().flush()?;
+            thread::sleep(*delay);
+        }
+        Animation::Frames(dir) => {
+            let file = dir.join(format!("frame_{:05}.ppm", frame));
+            fs::write(file, grid.to_ppm(min, max, 4))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn fill_grid(contents: &str, grid: &mut Grid) {
     contents
         .lines()
-        .map(|line| parse_points(&line))
+        .map(parse_points)
         .for_each(|points| grid.add_path(&points));
 }
 
@@ -145,11 +540,10 @@ fn parse_points(line: &str) -> Vec<Point> {
     let points = line.split("->");
 
     points
-        .into_iter()
         .map(|point| {
-            let mut split = point.trim().split(",");
-            let x = split.next().unwrap().parse::<usize>().unwrap();
-            let y = split.next().unwrap().parse::<usize>().unwrap();
+            let mut split = point.trim().split(',');
+            let x = split.next().unwrap().parse::<i32>().unwrap();
+            let y = split.next().unwrap().parse::<i32>().unwrap();
 
             Point::new(x, y)
         })
@@ -1,7 +1,11 @@
-use std::{cmp, fs};
+use std::{cmp, collections::HashSet, env, fs};
+
+/// The tuning frequency is x * 4000000 + y, whatever size the search area is.
+const TUNING_MULTIPLIER: i128 = 4_000_000;
 
 struct Grid {
     map: Vec<(i128, i128)>,
+    beacons: HashSet<i128>,
     target_line: i128,
 }
 
@@ -9,61 +13,80 @@ impl Grid {
     fn new(target_line: i128) -> Grid {
         Grid {
             map: vec![],
+            beacons: HashSet::new(),
             target_line,
         }
     }
 
     fn add_sb_pair(&mut self, sensor: &Point, beacon: &Point) {
-        let distance = (sensor.x - beacon.x).abs() + (sensor.y - beacon.y).abs();
-        let dist_to_target = (sensor.y - self.target_line).abs();
-        let remaining_distance = distance - dist_to_target;
-
-        if remaining_distance < 0 {
-            return;
+        if let Some(block) = Sensor::new(*sensor, *beacon).row_coverage(self.target_line) {
+            self.map.push(block);
+        }
+        if beacon.y == self.target_line {
+            self.beacons.insert(beacon.x);
         }
-
-        let blockout = (sensor.x - remaining_distance, sensor.x + remaining_distance);
-        self.add_block(blockout);
     }
 
-    fn add_block(&mut self, new_block: (i128, i128)) {
-        self.map.push(new_block);
-        self.map.sort_by(|b1, b2| b1.0.cmp(&b2.0));
-        self.merge_blocks();
-    }
+    /// Cells on the target row that can't hold a beacon. The beacons already
+    /// there are covered by their own sensor, so they're taken back out.
+    fn get_blocked_count(&self) -> i128 {
+        let covered = Intervals::new(self.map.clone());
+        let beacons = self.beacons.iter().filter(|&&x| covered.contains(x));
 
-    fn merge_blocks(&mut self) {
-        let mut items_to_remove = vec![];
+        covered.len() - beacons.count() as i128
+    }
+}
 
-        for i in 0..self.map.len() - 1 {
-            if items_to_remove.contains(&i) {
-                continue;
-            }
+/// A union of inclusive ranges, kept sorted with none overlapping or touching.
+struct Intervals {
+    ranges: Vec<(i128, i128)>,
+}
 
-            for j in i + 1..self.map.len() {
-                if blocks_overlap(self.map[i], self.map[j]) {
-                    let min = cmp::min(self.map[i].0, self.map[j].0);
-                    let max = cmp::max(self.map[i].1, self.map[j].1);
+impl Intervals {
+    fn new(mut ranges: Vec<(i128, i128)>) -> Intervals {
+        ranges.sort_unstable();
 
-                    self.map[i] = (min, max);
-                    items_to_remove.push(j);
-                }
+        let mut merged: Vec<(i128, i128)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 + 1 => last.1 = cmp::max(last.1, end),
+                _ => merged.push((start, end)),
             }
         }
 
-        items_to_remove.sort_by(|a, b| b.cmp(&a));
-        items_to_remove.iter().for_each(|index| {
-            self.map.remove(*index);
-        });
+        Intervals { ranges: merged }
     }
 
-    fn get_blocked_count(&self) -> i128 {
-        self.map.iter().map(|block| block.1 - block.0).sum()
+    /// Number of cells covered.
+    fn len(&self) -> i128 {
+        self.ranges.iter().map(|(start, end)| end - start + 1).sum()
     }
-}
 
-fn blocks_overlap(b1: (i128, i128), b2: (i128, i128)) -> bool {
-    b1.1 >= b2.0 && b1.1 <= b2.1 || b1.0 >= b2.0 && b1.0 <= b2.1 || b1.0 <= b2.0 && b1.1 >= b2.1
+    fn contains(&self, x: i128) -> bool {
+        let after = self.ranges.partition_point(|&(start, _)| start <= x);
+        after > 0 && x <= self.ranges[after - 1].1
+    }
+
+    /// The ranges within `min..=max` that aren't covered.
+    fn gaps(&self, min: i128, max: i128) -> Vec<(i128, i128)> {
+        let mut gaps = vec![];
+        let mut next = min;
+
+        for &(start, end) in &self.ranges {
+            if start > max {
+                break;
+            }
+            if start > next {
+                gaps.push((next, start - 1));
+            }
+            next = cmp::max(next, end + 1);
+        }
+        if next <= max {
+            gaps.push((next, max));
+        }
+
+        gaps
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
@@ -76,10 +99,16 @@ impl Point {
     fn new(x: i128, y: i128) -> Point {
         Point { x, y }
     }
+
+    /// (x + y, x - y), which turns a sensor's diamond into a square.
+    fn rotated(&self) -> (i128, i128) {
+        (self.x + self.y, self.x - self.y)
+    }
 }
 
 struct Sensor {
     sensor: Point,
+    beacon: Point,
     distance: i128,
 }
 
@@ -87,6 +116,7 @@ impl Sensor {
     fn new(sensor: Point, beacon: Point) -> Sensor {
         Sensor {
             sensor,
+            beacon,
             distance: (sensor.x - beacon.x).abs() + (sensor.y - beacon.y).abs(),
         }
     }
@@ -95,43 +125,316 @@ impl Sensor {
         self.distance + 1
     }
 
+    /// The cells this sensor covers on row `y`, if it reaches that far.
+    fn row_coverage(&self, y: i128) -> Option<(i128, i128)> {
+        let remaining = self.distance - (self.sensor.y - y).abs();
+        (remaining >= 0).then(|| (self.sensor.x - remaining, self.sensor.x + remaining))
+    }
+
+    fn column_coverage(&self, x: i128) -> Option<(i128, i128)> {
+        let remaining = self.distance - (self.sensor.x - x).abs();
+        (remaining >= 0).then(|| (self.sensor.y - remaining, self.sensor.y + remaining))
+    }
+
     fn contains_point(&self, point: &Point) -> bool {
         let dist_to_point = (self.sensor.x - point.x).abs() + (self.sensor.y - point.y).abs();
         dist_to_point <= self.distance
     }
 }
 
+struct Config {
+    path: String,
+    target_line: i128,
+    search_max: i128,
+    sweep: bool,
+    image: Option<String>,
+    resolution: i128,
+}
+
+impl Config {
+    /// Usage: rust_sol [--example] [--row <y>] [--max <n>] [--sweep]
+    ///                 [--image <file.ppm>] [--resolution <pixels>] [input file]
+    ///
+    /// Part 1 counts the blocked cells on row `--row`, part 2 searches 0..=`--max`
+    /// in both directions. The real input uses row 2000000 and 4000000; `--example`
+    /// switches to the puzzle's example values (row 10 and 20) and reads test.txt
+    /// unless a file is given. `--sweep` finds part 2 by checking every row.
+    /// `--image` draws the search area's coverage, `--resolution` pixels
+    /// square (1000 by default).
+    fn from_args() -> Config {
+        let mut example = false;
+        let mut sweep = false;
+        let mut target_line = None;
+        let mut search_max = None;
+        let mut path = None;
+        let mut image = None;
+        let mut resolution = 1000;
+        let mut args = env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--example" => example = true,
+                "--sweep" => sweep = true,
+                "--row" => {
+                    let row = args.next().and_then(|row| row.parse().ok());
+                    target_line = Some(row.expect("--row needs a row number."));
+                }
+                "--max" => {
+                    let max = args.next().and_then(|max| max.parse().ok());
+                    search_max = Some(max.expect("--max needs a coordinate."));
+                }
+                "--image" => image = Some(args.next().expect("--image needs a file name.")),
+                "--resolution" => {
+                    resolution = args
+                        .next()
+                        .and_then(|pixels| pixels.parse().ok())
+                        .filter(|&pixels| pixels > 0)
+                        .expect("--resolution needs a number of pixels.");
+                }
+                _ => path = Some(arg),
+            }
+        }
+
+        let (default_path, default_line, default_max) = if example {
+            ("test.txt", 10, 20)
+        } else {
+            ("input.txt", 2_000_000, 4_000_000)
+        };
+
+        Config {
+            path: path.unwrap_or_else(|| default_path.to_string()),
+            target_line: target_line.unwrap_or(default_line),
+            search_max: search_max.unwrap_or(default_max),
+            sweep,
+            image,
+            resolution,
+        }
+    }
+}
+
 fn main() {
-    let contents = fs::read_to_string("input.txt").expect("Couldn't read file.");
-    let grid = fill_grid(&contents, 10);
+    let config = Config::from_args();
+    let contents = fs::read_to_string(&config.path).expect("Couldn't read file.");
+    let grid = fill_grid(&contents, config.target_line);
     let blocked_count = grid.get_blocked_count();
     println!("Answer 1: {blocked_count}");
 
     let sensors = get_sensor_list(&contents);
+    let beacon = if config.sweep {
+        unique_position(sweep_rows(&sensors, config.search_max), &sensors)
+    } else {
+        unique_position(find_on_edges(&sensors, config.search_max), &sensors)
+    };
+
+    if let Some(image) = &config.image {
+        let gap = beacon.as_ref().ok();
+        let ppm = coverage_ppm(&sensors, gap, config.search_max, config.resolution);
+        fs::write(image, ppm).expect("Couldn't write image.");
+    }
 
-    let mut candidate_points = vec![];
-    sensors.iter().for_each(|s1| {
-        sensors.iter().for_each(|s2| {
-            if s1.sensor != s2.sensor {
-                let mut interect = get_intersections(&s1, &s2);
-                candidate_points.append(&mut interect);
-            }
-        });
+    let beacon = beacon.expect("Couldn't find the distress beacon.");
+
+    let tuning_freq = beacon.x * TUNING_MULTIPLIER + beacon.y;
+    println!("Answer 2: {tuning_freq}");
+}
+
+/// The only cell in the search area no sensor covers, or why there isn't one.
+fn unique_position(
+    candidates: impl Iterator<Item = Point>,
+    sensors: &[Sensor],
+) -> Result<Point, String> {
+    let mut found = HashSet::new();
+
+    for point in candidates.filter(|point| !is_in_range(point, sensors)) {
+        found.insert(point);
+        if found.len() > 1 {
+            let points: Vec<String> = found.iter().map(|p| format!("{},{}", p.x, p.y)).collect();
+            return Err(format!("more than one position: {}", points.join(" and ")));
+        }
+    }
+
+    found
+        .into_iter()
+        .next()
+        .ok_or_else(|| String::from("every position is covered"))
+}
+
+/// Checks every row of the search area for a cell no sensor covers. Slow, but
+/// doesn't rely on the gap sitting where sensor ranges meet.
+fn sweep_rows(sensors: &[Sensor], max: i128) -> impl Iterator<Item = Point> + '_ {
+    (0..=max).flat_map(move |y| {
+        let covered = Intervals::new(
+            sensors
+                .iter()
+                .filter_map(|sensor| sensor.row_coverage(y))
+                .collect(),
+        );
+
+        covered
+            .gaps(0, max)
+            .into_iter()
+            .flat_map(move |(start, end)| (start..=end).map(move |x| Point::new(x, y)))
+    })
+}
+
+/// Every uncovered cell next to a covered one sits just outside some sensor's
+/// range. Turned 45 degrees with u = x + y and v = x - y, each range is a square,
+/// so those cells all lie on a line u or v = constant one step past a side, and
+/// the gaps along each of those lines can be found like a row. Cells with no
+/// covered neighbour at all have to be on the edge of the search area, so its
+/// four sides are checked too.
+fn find_on_edges(sensors: &[Sensor], max: i128) -> impl Iterator<Item = Point> + '_ {
+    let lines = sensors.iter().flat_map(|sensor| {
+        let (u, v) = sensor.sensor.rotated();
+        let reach = sensor.get_perimiter();
+
+        [
+            Line::U(u - reach),
+            Line::U(u + reach),
+            Line::V(v - reach),
+            Line::V(v + reach),
+        ]
     });
+    let sides = [
+        Line::Row(0),
+        Line::Row(max),
+        Line::Column(0),
+        Line::Column(max),
+    ];
+
+    sides
+        .into_iter()
+        .chain(lines)
+        .flat_map(move |line| line.gaps(sensors, max))
+}
 
-    let filtered: Vec<&Point> = candidate_points
-        .iter()
-        .filter(|point| {
-            point.x >= 0 && point.x <= 4_000_000 && point.y >= 0 && point.y <= 4_000_000
+/// A line through the search area, either a row or column or a diagonal in
+/// rotated coordinates.
+#[derive(Clone, Copy)]
+enum Line {
+    Row(i128),
+    Column(i128),
+    U(i128),
+    V(i128),
+}
+
+impl Line {
+    /// The cells on this line within 0..=max no sensor covers.
+    fn gaps(self, sensors: &[Sensor], max: i128) -> impl Iterator<Item = Point> {
+        // where along the line each sensor's range covers, and the part of the
+        // line inside the search area, measured in the same coordinate
+        let (covered, (start, end)): (Vec<(i128, i128)>, (i128, i128)) = match self {
+            Line::Row(y) => (
+                sensors.iter().filter_map(|s| s.row_coverage(y)).collect(),
+                (0, max),
+            ),
+            Line::Column(x) => (
+                sensors
+                    .iter()
+                    .filter_map(|s| s.column_coverage(x))
+                    .collect(),
+                (0, max),
+            ),
+            Line::U(c) | Line::V(c) => {
+                let covered = sensors
+                    .iter()
+                    .filter_map(|sensor| {
+                        let (u, v) = sensor.sensor.rotated();
+                        let (across, along) = match self {
+                            Line::U(_) => (u, v),
+                            _ => (v, u),
+                        };
+                        ((c - across).abs() <= sensor.distance)
+                            .then(|| (along - sensor.distance, along + sensor.distance))
+                    })
+                    .collect();
+
+                // x runs over whatever keeps y = c - x (or x - c) in range, and
+                // the other rotated coordinate is 2x - c
+                let (min_x, max_x) = match self {
+                    Line::U(_) => (cmp::max(0, c - max), cmp::min(max, c)),
+                    _ => (cmp::max(0, c), cmp::min(max, max + c)),
+                };
+                (covered, (2 * min_x - c, 2 * max_x - c))
+            }
+        };
+
+        let gaps = if start <= end {
+            Intervals::new(covered).gaps(start, end)
+        } else {
+            vec![]
+        };
+
+        gaps.into_iter().flat_map(move |(from, to)| {
+            let cells = match self {
+                Line::Row(_) | Line::Column(_) => (from..=to).step_by(1),
+                // only every other value lands on a whole cell
+                Line::U(c) | Line::V(c) => (from + (from - c).rem_euclid(2)..=to).step_by(2),
+            };
+
+            cells.map(move |w| match self {
+                Line::Row(y) => Point::new(w, y),
+                Line::Column(x) => Point::new(x, w),
+                Line::U(c) => Point::new((c + w) / 2, (c - w) / 2),
+                Line::V(c) => Point::new((c + w) / 2, (w - c) / 2),
+            })
+        })
+    }
+}
+
+/// The search area shrunk (or stretched) to `resolution` pixels square. Each
+/// pixel takes the colour of the first sensor covering the cell at its centre,
+/// or stays black if none do, so a lone gap is usually too small to show up on
+/// its own; beacons and the gap are marked on top.
+fn coverage_ppm(sensors: &[Sensor], gap: Option<&Point>, max: i128, resolution: i128) -> Vec<u8> {
+    const PALETTE: [[u8; 3]; 6] = [
+        [38, 139, 210],
+        [42, 161, 152],
+        [133, 153, 0],
+        [181, 137, 0],
+        [108, 113, 196],
+        [211, 54, 130],
+    ];
+    let size = max + 1;
+    let to_cell = |pixel: i128| (2 * pixel + 1) * size / (2 * resolution);
+    let to_pixel = |cell: i128| cell * resolution / size;
+
+    let mut pixels: Vec<[u8; 3]> = (0..resolution)
+        .flat_map(|py| (0..resolution).map(move |px| Point::new(to_cell(px), to_cell(py))))
+        .map(|cell| {
+            sensors
+                .iter()
+                .position(|sensor| sensor.contains_point(&cell))
+                .map_or([0, 0, 0], |index| PALETTE[index % PALETTE.len()])
         })
-        .filter(|point| !is_in_range(point, &sensors))
         .collect();
 
-    let tuning_freq = filtered[0].x * 4_000_000 + filtered[0].y;
-    println!("Answer 2: {tuning_freq}");
+    let mut mark = |point: &Point, color: [u8; 3]| {
+        let arm = cmp::max(1, resolution / 200);
+        let (px, py) = (to_pixel(point.x), to_pixel(point.y));
+
+        (-arm..=arm)
+            .flat_map(|d| [(px + d, py), (px, py + d)])
+            .filter(|&(x, y)| (0..resolution).contains(&x) && (0..resolution).contains(&y))
+            .for_each(|(x, y)| pixels[(y * resolution + x) as usize] = color);
+    };
+
+    sensors
+        .iter()
+        .filter(|s| (0..=max).contains(&s.beacon.x) && (0..=max).contains(&s.beacon.y))
+        .for_each(|s| mark(&s.beacon, [255, 255, 255]));
+    if let Some(gap) = gap {
+        mark(gap, [220, 50, 47]);
+    }
+
+    let mut image = format!("P6\n{} {}\n255\n", resolution, resolution).into_bytes();
+    pixels
+        .iter()
+        .for_each(|color| image.extend_from_slice(color));
+    image
 }
 
-fn is_in_range(point: &Point, sensors: &Vec<Sensor>) -> bool {
+fn is_in_range(point: &Point, sensors: &[Sensor]) -> bool {
     for sensor in sensors {
         if sensor.contains_point(point) {
             return true;
@@ -140,57 +443,22 @@ fn is_in_range(point: &Point, sensors: &Vec<Sensor>) -> bool {
     false
 }
 
-fn get_intersections(s1: &Sensor, s2: &Sensor) -> Vec<Point> {
-    let mut intersect = get_points(s1, s2);
-    let mut other = get_points(s2, s1);
-    intersect.append(&mut other);
-    intersect
-}
-
-fn get_points(s1: &Sensor, s2: &Sensor) -> Vec<Point> {
-    [
-        (
-            (s1.sensor.x - s1.get_perimiter()),
-            (s2.sensor.x - s2.get_perimiter()),
-        ),
-        (
-            (s1.sensor.x - s1.get_perimiter()),
-            (s2.sensor.x + s2.get_perimiter()),
-        ),
-        (
-            (s1.sensor.x + s1.get_perimiter()),
-            (s2.sensor.x - s2.get_perimiter()),
-        ),
-        (
-            (s1.sensor.x + s1.get_perimiter()),
-            (s2.sensor.x + s2.get_perimiter()),
-        ),
-    ]
-    .into_iter()
-    .map(|(dx1, dx2)| {
-        let x = (dx2 + s2.sensor.y + dx1 - s1.sensor.y) / 2;
-        let y = (dx2 + s2.sensor.y - dx1 + s1.sensor.y) / 2;
-        Point::new(x, y)
-    })
-    .collect()
-}
-
 fn get_sensor_list(contents: &str) -> Vec<Sensor> {
     let mut sensors = vec![];
 
     contents
         .lines()
-        .map(|line| parse_points(&line))
+        .map(parse_points)
         .for_each(|(sensor, beacon)| sensors.push(Sensor::new(sensor, beacon)));
 
     sensors
 }
 
-fn fill_grid(contents: &String, target_line: i128) -> Grid {
+fn fill_grid(contents: &str, target_line: i128) -> Grid {
     let mut grid = Grid::new(target_line);
     contents
         .lines()
-        .map(|line| parse_points(&line))
+        .map(parse_points)
         .for_each(|(sensor, beacon)| {
             grid.add_sb_pair(&sensor, &beacon);
         });
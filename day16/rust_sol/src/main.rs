@@ -1,14 +1,10 @@
-use std::{
-    collections::{HashMap, HashSet},
-    fs,
-};
+use std::{cmp::Reverse, collections::HashMap, fs};
 
 #[derive(Debug, Clone)]
 struct Valve {
     id: String,
     flow_rate: i32,
     adj_list: Vec<String>,
-    distances: HashMap<String, i32>,
 }
 
 impl Valve {
@@ -17,184 +13,193 @@ impl Valve {
             id,
             flow_rate,
             adj_list,
-            distances: HashMap::new(),
         }
     }
+}
+
+/// The valves numbered by their order in the input. Only the ones with some
+/// flow are worth walking to, so sets of those are kept as bitmasks, with bit
+/// `i` standing for `useful[i]`.
+struct Cave {
+    flow: Vec<i32>,
+    /// Minutes from valve `a` to valve `b` at `a * size + b`.
+    dist: Vec<i32>,
+    size: usize,
+    useful: Vec<usize>,
+    start: usize,
+}
+
+impl Cave {
+    fn new(valves: &[Valve]) -> Self {
+        let size = valves.len();
+        let index: HashMap<&str, usize> = valves
+            .iter()
+            .enumerate()
+            .map(|(i, valve)| (valve.id.as_str(), i))
+            .collect();
+
+        // Floyd-Warshall, every tunnel takes a minute
+        let mut dist = vec![i32::MAX / 2; size * size];
+        valves.iter().enumerate().for_each(|(a, valve)| {
+            dist[a * size + a] = 0;
+            valve
+                .adj_list
+                .iter()
+                .for_each(|adj| dist[a * size + index[adj.as_str()]] = 1);
+        });
+        for k in 0..size {
+            for a in 0..size {
+                for b in 0..size {
+                    let through = dist[a * size + k] + dist[k * size + b];
+                    if through < dist[a * size + b] {
+                        dist[a * size + b] = through;
+                    }
+                }
+            }
+        }
+
+        let useful: Vec<usize> = (0..size).filter(|&i| valves[i].flow_rate != 0).collect();
+        assert!(
+            useful.len() <= 64,
+            "Too many valves with flow for a u64 set."
+        );
+
+        Cave {
+            flow: valves.iter().map(|valve| valve.flow_rate).collect(),
+            dist,
+            size,
+            useful,
+            start: index["AA"],
+        }
+    }
+
+    fn distance(&self, from: usize, to: usize) -> i32 {
+        self.dist[from * self.size + to]
+    }
 
-    fn set_distances(&mut self, distances: HashMap<String, i32>) {
-        self.distances = distances;
+    /// Every useful valve.
+    fn all(&self) -> u64 {
+        u64::MAX
+            .checked_shr(64 - self.useful.len() as u32)
+            .unwrap_or(0)
     }
 }
 
 fn main() {
     let contents = fs::read_to_string("input.txt").expect("Couldn't read file.");
-    let graph = build_graph(&contents);
+    let valves: Vec<Valve> = contents.lines().map(parse_line).collect();
+    let cave = Cave::new(&valves);
     let timeout = 30;
 
-    let (p1_flow_total, p2_flow_total) = run_sim(graph, timeout);
+    let (p1_flow_total, p2_flow_total) = run_sim(&cave, timeout);
 
     println!("Answer 1: {p1_flow_total}");
     println!("Answer 2: {p2_flow_total}");
 }
 
-fn run_sim(graph: HashMap<String, Valve>, timeout: i32) -> (i32, i32) {
-    let root = "AA";
-    let nodes = graph
-        .iter()
-        .filter(|(_, val)| val.flow_rate != 0)
-        .map(|(key, _)| key.clone())
-        .collect();
-
-    let mut cache: HashMap<(String, Vec<String>, i32), i32> = HashMap::new();
-    let part1 = get_max_flow(&graph, root, timeout, nodes, &mut cache);
-
-    let part2 = get_permutations(&graph)
-        .into_iter()
-        .map(|(s1, s2)| {
-            if s1.is_empty() || s2.is_empty() {
-                return 0;
-            }
-            let elf_flow = get_max_flow(&graph, root, 26, s1, &mut cache);
-            if elf_flow < part1 / 2 {
-                return 0;
-            }
-            let eleph_flow = get_max_flow(&graph, root, 26, s2, &mut cache);
-            elf_flow + eleph_flow
-        })
-        .max()
-        .unwrap();
-
-    (part1, part2)
-}
+fn run_sim(cave: &Cave, timeout: i32) -> (i32, i32) {
+    let mut cache = HashMap::new();
+    let part1 = get_max_flow(cave, cave.start, timeout, cave.all(), &mut cache);
 
-fn get_permutations(graph: &HashMap<String, Valve>) -> Vec<(Vec<String>, Vec<String>)> {
-    let nodes = graph
-        .iter()
-        .filter(|(_, val)| val.flow_rate != 0)
-        .map(|(key, _)| key.clone())
-        .collect();
-    get_subsets(nodes)
-}
+    // we and the elephant open different valves, so the best team is the best
+    // pair of disjoint sets. Going through them best first, a pair can stop as
+    // soon as it can't beat what's already found, since everything after it
+    // scores less.
+    let mut sets: Vec<(u64, i32)> = best_per_set(cave, 26).into_iter().collect();
+    sets.sort_unstable_by_key(|&(_, flow)| Reverse(flow));
 
-fn get_subsets(nodes: Vec<String>) -> Vec<(Vec<String>, Vec<String>)> {
-    let mut result: Vec<Vec<String>> = vec![vec![nodes[0].clone()]];
-
-    for node in &nodes[1..] {
-        let mut new_result = result.clone();
-        for r in result {
-            let mut cloned = r.clone();
-            cloned.push(node.clone());
-            new_result.push(cloned);
+    let mut part2 = 0;
+    for (i, &(elf_valves, elf_flow)) in sets.iter().enumerate() {
+        if elf_flow * 2 <= part2 {
+            break;
+        }
+        for &(eleph_valves, eleph_flow) in &sets[i..] {
+            if elf_flow + eleph_flow <= part2 {
+                break;
+            }
+            if elf_valves & eleph_valves == 0 {
+                part2 = elf_flow + eleph_flow;
+            }
         }
-        result = new_result;
     }
 
-    result
-        .into_iter()
-        .map(|left| {
-            let nodes_cloned = nodes.clone();
-            let right = nodes_cloned
-                .into_iter()
-                .filter(|x| !left.contains(x))
-                .collect();
-
-            (left, right)
-        })
-        .collect()
+    (part1, part2)
 }
 
 fn get_max_flow(
-    graph: &HashMap<String, Valve>,
-    start: &str,
+    cave: &Cave,
+    start: usize,
     time_remaining: i32,
-    valves_to_visit: Vec<String>,
-    cache: &mut HashMap<(String, Vec<String>, i32), i32>,
+    valves_to_visit: u64,
+    cache: &mut HashMap<(usize, i32, u64), i32>,
 ) -> i32 {
+    let key = (start, time_remaining, valves_to_visit);
+    if let Some(&val) = cache.get(&key) {
+        return val;
+    }
+
     let mut max_flow = 0;
+    let mut remaining = valves_to_visit;
 
-    let key = (String::from(start), valves_to_visit.clone(), time_remaining);
-    if let Some(val) = cache.get(&key) {
-        return *val;
-    }
+    while remaining != 0 {
+        let bit = remaining.trailing_zeros() as usize;
+        remaining &= remaining - 1;
 
-    valves_to_visit.iter().enumerate().for_each(|(i, valve)| {
-        let dist = (&graph[start].distances)[valve] + 1;
-        let curr_time_remaining = time_remaining - dist;
+        let valve = cave.useful[bit];
+        let curr_time_remaining = time_remaining - cave.distance(start, valve) - 1;
 
         if curr_time_remaining > 0 {
-            let mut curr_valves_to_visit = valves_to_visit.clone();
-            curr_valves_to_visit.remove(i);
-
             let curr_flow = get_max_flow(
-                &graph,
+                cave,
                 valve,
                 curr_time_remaining,
-                curr_valves_to_visit,
+                valves_to_visit & !(1 << bit),
                 cache,
             );
 
-            let curr_max_flow = curr_flow + get_edge_weight(&graph[valve], curr_time_remaining);
+            let curr_max_flow = curr_flow + curr_time_remaining * cave.flow[valve];
             max_flow = max_flow.max(curr_max_flow);
         }
-    });
+    }
 
     cache.insert(key, max_flow);
     max_flow
 }
 
-fn get_edge_weight(to_valve: &Valve, time_left: i32) -> i32 {
-    time_left * to_valve.flow_rate
+/// The most pressure one agent can release in `time` minutes, for each set of
+/// valves it can open in that time. Sets it can't get through aren't stored.
+fn best_per_set(cave: &Cave, time: i32) -> HashMap<u64, i32> {
+    let mut best = HashMap::new();
+    visit_all(cave, cave.start, time, 0, 0, &mut best);
+    best
 }
 
-fn bfs(graph: &HashMap<String, Valve>, src: &str) -> HashMap<String, i32> {
-    let mut visited = HashSet::new();
-    let mut queue = vec![src];
-    let mut distances = HashMap::new();
-
-    graph.keys().for_each(|key| {
-        distances.insert(key.clone(), i32::MAX);
-    });
-
-    distances.insert(src.to_string(), 0);
-    visited.insert(src.to_string());
-
-    while !queue.is_empty() {
-        let node = queue.remove(0);
-
-        graph[node].adj_list.iter().for_each(|adj_node| {
-            if !visited.contains(adj_node) {
-                visited.insert(adj_node.clone());
-                distances.insert(adj_node.clone(), distances[node] + 1);
-                queue.push(&adj_node);
-            }
-        });
+fn visit_all(
+    cave: &Cave,
+    at: usize,
+    time_remaining: i32,
+    opened: u64,
+    flow: i32,
+    best: &mut HashMap<u64, i32>,
+) {
+    let set_best = best.entry(opened).or_insert(0);
+    *set_best = (*set_best).max(flow);
+
+    for (bit, &valve) in cave.useful.iter().enumerate() {
+        let curr_time_remaining = time_remaining - cave.distance(at, valve) - 1;
+
+        if opened & (1 << bit) == 0 && curr_time_remaining > 0 {
+            let curr_flow = flow + curr_time_remaining * cave.flow[valve];
+            visit_all(
+                cave,
+                valve,
+                curr_time_remaining,
+                opened | (1 << bit),
+                curr_flow,
+                best,
+            );
+        }
     }
-
-    distances
-        .into_iter()
-        .filter(|(key, _)| graph[key].flow_rate != 0)
-        .collect()
-}
-
-fn build_graph(contents: &str) -> HashMap<String, Valve> {
-    let mut graph = HashMap::new();
-    contents
-        .lines()
-        .map(|line| parse_line(line))
-        .for_each(|valve| {
-            let id = valve.id.clone();
-            graph.insert(id, valve);
-        });
-
-    // store the distance from N to every other node with non-zero flow
-    // in N for easy lookup
-    let g2 = graph.clone();
-    graph.iter_mut().for_each(|(id, valve)| {
-        let dist = bfs(&g2, &id);
-        valve.set_distances(dist);
-    });
-
-    graph
 }
 
 fn parse_line(line: &str) -> Valve {
@@ -210,7 +215,7 @@ fn parse_line(line: &str) -> Valve {
     let flow_rate = split.next().unwrap().parse::<i32>().unwrap();
 
     let mut adj_list = vec![];
-    for elem in split.into_iter().rev() {
+    for elem in split.rev() {
         if elem == "valves" || elem == "valve" {
             break;
         }
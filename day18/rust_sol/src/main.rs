@@ -1,3 +1,4 @@
+use pathfinding::bfs_distances;
 use std::fs;
 
 const GRID_SIZE: usize = 24;
@@ -19,9 +20,7 @@ impl Point {
 enum BlockType {
     Air,
     Lava,
-    W1,
-    W2,
-    W3,
+    Water,
 }
 
 struct Map {
@@ -113,118 +112,42 @@ impl Map {
     }
 
     fn remove_air(&mut self) {
-        // slice in all three directions to fill in any air pockets
-        (0..GRID_SIZE).for_each(|idx| self.fill_water_xy(0, 0, idx));
-        (0..GRID_SIZE).for_each(|idx| self.fill_water_xz(0, idx, 0));
-        (0..GRID_SIZE).for_each(|idx| self.fill_water_yz(idx, 0, 0));
-
-        // remove all of the non water elements.
-        let new_coordinates: Vec<(i32, i32, i32)> = self
-            .side_coordinates
-            .iter()
-            .filter(|(x, y, z)| {
-                // out of bounds or not water
-                *x < 0
-                    || *y < 0
-                    || *z < 0
-                    || self.grid[*x as usize][*y as usize][*z as usize] == BlockType::W1
-                    || self.grid[*x as usize][*y as usize][*z as usize] == BlockType::W2
-                    || self.grid[*x as usize][*y as usize][*z as usize] == BlockType::W3
+        // flood the space around the droplet from a corner outside of it, any
+        // air the water can't reach is trapped inside
+        let bound = GRID_SIZE as i32;
+        let neighbours = |&(x, y, z): &(i32, i32, i32)| {
+            [
+                (x + 1, y, z),
+                (x - 1, y, z),
+                (x, y + 1, z),
+                (x, y - 1, z),
+                (x, y, z + 1),
+                (x, y, z - 1),
+            ]
+            .into_iter()
+            .filter(|&(x, y, z)| {
+                (-1..bound).contains(&x) && (-1..bound).contains(&y) && (-1..bound).contains(&z)
             })
-            .map(|coordinate| coordinate.clone())
-            .collect();
-
-        self.side_coordinates = new_coordinates;
-    }
-
-    fn fill_water_xy(&mut self, x: usize, y: usize, z: usize) {
-        self.grid[x][y][z] = BlockType::W1;
-
-        // fill up y direction
-        if y < GRID_SIZE - 1 && self.grid[x][y + 1][z] == BlockType::Air {
-            self.fill_water_xy(x, y + 1, z);
-        }
-        // fill down y direction
-        if y > 0 && self.grid[x][y - 1][z] == BlockType::Air {
-            self.fill_water_xy(x, y - 1, z);
-        }
-
-        // fill up x direction
-        if x < GRID_SIZE - 1 && self.grid[x + 1][y][z] == BlockType::Air {
-            self.fill_water_xy(x + 1, y, z);
-        }
-        // fill down x direction
-        if x > 0 && self.grid[x - 1][y][z] == BlockType::Air {
-            self.fill_water_xy(x - 1, y, z);
-        }
-    }
-
-    fn fill_water_xz(&mut self, x: usize, y: usize, z: usize) {
-        self.grid[x][y][z] = BlockType::W2;
-
-        // fill up x direction
-        if x < GRID_SIZE - 1
-            && (self.grid[x + 1][y][z] == BlockType::Air || self.grid[x + 1][y][z] == BlockType::W1)
-        {
-            self.fill_water_xz(x + 1, y, z);
-        }
-        // fill down x direction
-        if x > 0
-            && (self.grid[x - 1][y][z] == BlockType::Air || self.grid[x - 1][y][z] == BlockType::W1)
-        {
-            self.fill_water_xz(x - 1, y, z);
-        }
-
-        // fill up z direction
-        if z < GRID_SIZE - 1
-            && (self.grid[x][y][z + 1] == BlockType::Air || self.grid[x][y][z + 1] == BlockType::W1)
-        {
-            self.fill_water_xz(x, y, z + 1);
-        }
-        // fill down z direction
-        if z > 0
-            && (self.grid[x][y][z - 1] == BlockType::Air || self.grid[x][y][z - 1] == BlockType::W1)
-        {
-            self.fill_water_xz(x, y, z - 1);
-        }
-    }
-
-    fn fill_water_yz(&mut self, x: usize, y: usize, z: usize) {
-        self.grid[x][y][z] = BlockType::W3;
-
-        // fill up z direction
-        if z < GRID_SIZE - 1
-            && (self.grid[x][y][z + 1] == BlockType::Air
-                || self.grid[x][y][z + 1] == BlockType::W1
-                || self.grid[x][y][z + 1] == BlockType::W2)
-        {
-            self.fill_water_yz(x, y, z + 1);
-        }
-        // fill down z direction
-        if z > 0
-            && (self.grid[x][y][z - 1] == BlockType::Air
-                || self.grid[x][y][z - 1] == BlockType::W1
-                || self.grid[x][y][z - 1] == BlockType::W2)
-        {
-            self.fill_water_yz(x, y, z - 1);
-        }
+            .filter(|&(x, y, z)| {
+                x < 0
+                    || y < 0
+                    || z < 0
+                    || self.grid[x as usize][y as usize][z as usize] != BlockType::Lava
+            })
+            .map(|side| (side, 1))
+            .collect()
+        };
+        let water = bfs_distances(&neighbours, [(-1, -1, -1)]);
+
+        water.keys().for_each(|&(x, y, z)| {
+            if x >= 0 && y >= 0 && z >= 0 {
+                self.grid[x as usize][y as usize][z as usize] = BlockType::Water;
+            }
+        });
 
-        // fill up y direction
-        if y < GRID_SIZE - 1
-            && (self.grid[x][y + 1][z] == BlockType::Air
-                || self.grid[x][y + 1][z] == BlockType::W1
-                || self.grid[x][y + 1][z] == BlockType::W2)
-        {
-            self.fill_water_yz(x, y + 1, z);
-        }
-        // fill down y direction
-        if y > 0
-            && (self.grid[x][y - 1][z] == BlockType::Air
-                || self.grid[x][y - 1][z] == BlockType::W1
-                || self.grid[x][y - 1][z] == BlockType::W2)
-        {
-            self.fill_water_yz(x, y - 1, z);
-        }
+        // remove all of the sides that face trapped air.
+        self.side_coordinates
+            .retain(|coordinate| water.contains_key(coordinate));
     }
 
     fn draw_grid(&self) {
@@ -237,9 +160,7 @@ impl Map {
                 row.iter().for_each(|elem| match elem {
                     BlockType::Air => print!("."),
                     BlockType::Lava => print!("#"),
-                    BlockType::W1 => print!("~"),
-                    BlockType::W2 => print!("~"),
-                    BlockType::W3 => print!("~"),
+                    BlockType::Water => print!("~"),
                 });
                 println!();
             });
@@ -1,4 +1,11 @@
-use std::{collections::HashSet, fs};
+use std::{
+    collections::HashSet,
+    env, fs,
+    io::{self, Write},
+    path::PathBuf,
+    thread,
+    time::Duration,
+};
 
 #[derive(PartialEq, Eq, Hash, Clone)]
 struct Point {
@@ -11,101 +18,302 @@ impl Point {
         Point { x, y }
     }
 
-    fn move_up(&mut self) {
-        self.y += 1;
+    fn shift(&mut self, dx: i32, dy: i32) {
+        self.x += dx;
+        self.y += dy;
     }
 
-    fn move_down(&mut self) {
-        self.y -= 1;
+    /// Moves one step toward the leader if it's no longer touching. Each axis
+    /// moves by the sign of the delta, so this works for any gap size.
+    fn follow(&mut self, leader: &Point) {
+        let (dx, dy) = (leader.x - self.x, leader.y - self.y);
+
+        if dx.abs() > 1 || dy.abs() > 1 {
+            self.shift(dx.signum(), dy.signum());
+        }
     }
 
-    fn move_left(&mut self) {
-        self.x -= 1;
+    fn copy(&self) -> Point {
+        Point::new(self.x, self.y)
     }
+}
+
+struct Rope {
+    knots: Vec<Point>,
+    visited: Vec<HashSet<Point>>,
+}
+
+impl Rope {
+    fn new(length: usize) -> Rope {
+        assert!(length > 0, "A rope needs at least one knot.");
+
+        let knots = vec![Point::new(0, 0); length];
+        let visited = knots
+            .iter()
+            .map(|knot| HashSet::from([knot.copy()]))
+            .collect();
 
-    fn move_right(&mut self) {
-        self.x += 1;
+        Rope { knots, visited }
     }
 
-    fn move_up_right(&mut self) {
-        self.move_up();
-        self.move_right();
+    fn move_head(&mut self, dx: i32, dy: i32) {
+        self.knots[0].shift(dx, dy);
+
+        (1..self.knots.len()).for_each(|index| {
+            let leader = self.knots[index - 1].copy();
+            self.knots[index].follow(&leader);
+        });
+
+        self.knots
+            .iter()
+            .zip(self.visited.iter_mut())
+            .for_each(|(knot, visited)| {
+                visited.insert(knot.copy());
+            });
     }
 
-    fn move_up_left(&mut self) {
-        self.move_up();
-        self.move_left();
+    fn apply(&mut self, instructions: &str) {
+        let (direction, move_count) = parse_line(instructions);
+        let (dx, dy) = direction_delta(direction);
+
+        (0..move_count).for_each(|_| self.move_head(dx, dy));
     }
 
-    fn move_down_left(&mut self) {
-        self.move_down();
-        self.move_left();
+    fn visited(&self, knot: usize) -> &HashSet<Point> {
+        &self.visited[knot]
     }
 
-    fn move_down_right(&mut self) {
-        self.move_down();
-        self.move_right();
+    fn tail_visited(&self) -> &HashSet<Point> {
+        self.visited.last().unwrap()
     }
 
-    fn copy(&self) -> Point {
-        Point::new(self.x, self.y)
+    fn knot_label(&self, index: usize) -> char {
+        match index {
+            0 => 'H',
+            _ if index == self.knots.len() - 1 => 'T',
+            1..=9 => char::from_digit(index as u32, 10).unwrap(),
+            _ => '+',
+        }
+    }
+
+    /// Draws the rope into rows (top row first). Knots closer to the head are
+    /// drawn over the ones behind them, and cells the tail has visited are `#`.
+    fn render(&self, bounds: &Bounds) -> Vec<Vec<char>> {
+        let mut grid = vec![vec!['.'; bounds.width()]; bounds.height()];
+
+        self.tail_visited().iter().for_each(|point| {
+            grid[(bounds.max_y - point.y) as usize][(point.x - bounds.min_x) as usize] = '#';
+        });
+
+        self.knots
+            .iter()
+            .enumerate()
+            .rev()
+            .for_each(|(index, knot)| {
+                grid[(bounds.max_y - knot.y) as usize][(knot.x - bounds.min_x) as usize] =
+                    self.knot_label(index);
+            });
+
+        grid
     }
 }
 
-fn main() {
-    let contents = fs::read_to_string("input.txt").expect("Couldn't read file.");
+struct Bounds {
+    min_x: i32,
+    max_x: i32,
+    min_y: i32,
+    max_y: i32,
+}
 
-    let mut two_knots = vec![Point::new(0, 0); 2];
-    let mut two_visited = HashSet::new();
-    two_visited.insert(two_knots[0].copy());
+impl Bounds {
+    /// Every knot follows the head, so the area the head covers holds the
+    /// whole animation.
+    fn of_moves(contents: &str) -> Bounds {
+        let mut head = Rope::new(1);
+        contents.lines().for_each(|line| head.apply(line));
 
-    let mut ten_knots = vec![Point::new(0, 0); 10];
-    let mut ten_visited = HashSet::new();
-    ten_visited.insert(ten_knots[0].copy());
+        let visited = head.visited(0);
+        Bounds {
+            min_x: visited.iter().map(|point| point.x).min().unwrap(),
+            max_x: visited.iter().map(|point| point.x).max().unwrap(),
+            min_y: visited.iter().map(|point| point.y).min().unwrap(),
+            max_y: visited.iter().map(|point| point.y).max().unwrap(),
+        }
+    }
 
-    contents.lines().for_each(|line| {
-        update_position(&mut two_knots, line, &mut two_visited);
-        update_position(&mut ten_knots, line, &mut ten_visited);
-    });
+    fn width(&self) -> usize {
+        (self.max_x - self.min_x + 1) as usize
+    }
+
+    fn height(&self) -> usize {
+        (self.max_y - self.min_y + 1) as usize
+    }
+}
 
-    println!("Answer 1: {}", two_visited.len());
-    println!("Answer 2: {}", ten_visited.len());
+enum Animation {
+    Terminal(Duration),
+    Frames(PathBuf),
 }
 
-fn update_position(knots: &mut Vec<Point>, instructions: &str, visited: &mut HashSet<Point>) {
-    let (direction, move_count) = parse_line(instructions);
+struct Config {
+    path: String,
+    lengths: Vec<usize>,
+    all_knots: bool,
+    animation: Option<Animation>,
+}
+
+impl Config {
+    /// Usage: rust_sol [--all-knots] [--animate <delay ms> | --frames <dir>]
+    ///                 [input file] [rope lengths...]
+    ///
+    /// Animations are drawn for the last rope length given.
+    fn from_args() -> Config {
+        let mut all_knots = false;
+        let mut animation = None;
+        let mut positional = Vec::new();
+        let mut args = env::args().skip(1);
 
-    (0..move_count).for_each(|_| {
-        match direction {
-            "U" => knots[0].move_up(),
-            "D" => knots[0].move_down(),
-            "L" => knots[0].move_left(),
-            "R" => knots[0].move_right(),
-            _ => (),
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--all-knots" => all_knots = true,
+                "--animate" => {
+                    let delay = args
+                        .next()
+                        .and_then(|delay| delay.parse().ok())
+                        .expect("--animate needs a delay in milliseconds.");
+                    animation = Some(Animation::Terminal(Duration::from_millis(delay)));
+                }
+                "--frames" => {
+                    let dir = args.next().expect("--frames needs an output directory.");
+                    animation = Some(Animation::Frames(PathBuf::from(dir)));
+                }
+                _ => positional.push(arg),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let path = positional.next().unwrap_or_else(|| "input.txt".to_string());
+        let mut lengths: Vec<usize> = positional
+            .map(|len| len.parse().expect("Couldn't parse rope length."))
+            .collect();
+
+        if lengths.is_empty() {
+            lengths = vec![2, 10];
+        }
+
+        Config {
+            path,
+            lengths,
+            all_knots,
+            animation,
+        }
+    }
+}
+
+fn main() {
+    let config = Config::from_args();
+    let contents = fs::read_to_string(&config.path).expect("Couldn't read file.");
+
+    let mut ropes: Vec<Rope> = config.lengths.iter().map(|&len| Rope::new(len)).collect();
+
+    contents.lines().for_each(|line| {
+        ropes.iter_mut().for_each(|rope| rope.apply(line));
+    });
+
+    ropes.iter().enumerate().for_each(|(index, rope)| {
+        println!("Answer {}: {}", index + 1, rope.tail_visited().len());
+
+        if config.all_knots {
+            (0..rope.knots.len()).for_each(|knot| {
+                println!("  knot {}: {}", knot, rope.visited(knot).len());
+            });
         }
-        update_knots(knots);
-        visited.insert(knots.last().unwrap().copy());
     });
+
+    if let Some(animation) = &config.animation {
+        let length = *config.lengths.last().unwrap();
+        animate(&contents, length, animation).expect("Couldn't write animation.");
+    }
 }
 
-fn update_knots(knots: &mut Vec<Point>) {
-    (1..knots.len()).for_each(|index| {
-        let diff = (knots[index-1].x - knots[index].x, knots[index-1].y - knots[index].y);
+fn animate(contents: &str, length: usize, animation: &Animation) -> io::Result<()> {
+    let bounds = Bounds::of_moves(contents);
+    let mut rope = Rope::new(length);
+    let mut frame = 0;
 
-        match diff {
-            (2, 0) => knots[index].move_right(),
-            (-2, 0) => knots[index].move_left(),
-            (0, 2) => knots[index].move_up(),
-            (0, -2) => knots[index].move_down(),
+    if let Animation::Frames(dir) = animation {
+        fs::create_dir_all(dir)?;
+    }
+    show_frame(&rope.render(&bounds), frame, animation)?;
 
-            (2, 1) | (1, 2) | (2, 2) => knots[index].move_up_right(),
-            (-2, 1) | (-1, 2) | (-2, 2) => knots[index].move_up_left(),
-            (2, -1) | (1, -2) | (2, -2) => knots[index].move_down_right(),
-            (-2, -1) | (-1, -2) | (-2, -2) => knots[index].move_down_left(),
+    for line in contents.lines() {
+        let (direction, move_count) = parse_line(line);
+        let (dx, dy) = direction_delta(direction);
 
-            (_, _) => (),
+        for _ in 0..move_count {
+            rope.move_head(dx, dy);
+            frame += 1;
+            show_frame(&rope.render(&bounds), frame, animation)?;
         }
+    }
+
+    Ok(())
+}
+
+fn show_frame(grid: &[Vec<char>], frame: usize, animation: &Animation) -> io::Result<()> {
+    let text: String = grid
+        .iter()
+        .map(|row| row.iter().collect::<String>() + "\n")
+        .collect();
+
+    match animation {
+        Animation::Terminal(delay) => {
+            // clear the screen and move the cursor home before each frame
+            print!("\x1B[2J\x1B[H{}", text);
+            io::stdout().flush()?;
+            thread::sleep(*delay);
+        }
+        Animation::Frames(dir) => {
+            fs::write(dir.join(format!("frame_{:05}.txt", frame)), text)?;
+            fs::write(dir.join(format!("frame_{:05}.ppm", frame)), to_ppm(grid, 4))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn to_ppm(grid: &[Vec<char>], scale: usize) -> Vec<u8> {
+    let (width, height) = (grid[0].len() * scale, grid.len() * scale);
+    let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+
+    (0..height).for_each(|y| {
+        (0..width).for_each(|x| {
+            let color: [u8; 3] = match grid[y / scale][x / scale] {
+                'H' => [220, 50, 47],
+                'T' => [38, 139, 210],
+                '#' => [88, 88, 88],
+                '.' => [0, 0, 0],
+                _ => [203, 75, 22],
+            };
+            image.extend_from_slice(&color);
+        });
     });
+
+    image
+}
+
+fn direction_delta(direction: &str) -> (i32, i32) {
+    match direction {
+        "U" => (0, 1),
+        "D" => (0, -1),
+        "L" => (-1, 0),
+        "R" => (1, 0),
+        "UL" => (-1, 1),
+        "UR" => (1, 1),
+        "DL" => (-1, -1),
+        "DR" => (1, -1),
+        _ => panic!("Unknown direction: {}", direction),
+    }
 }
 
 fn parse_line(instructions: &str) -> (&str, usize) {
@@ -0,0 +1,196 @@
+//! Shortest paths shared by the grid and graph days.
+//!
+//! A graph is anything that can list the neighbours of a node along with the
+//! cost of stepping to each one, so a closure over a grid works as well as an
+//! adjacency list.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    hash::Hash,
+};
+
+pub trait Graph<N> {
+    fn neighbours(&self, node: &N) -> Vec<(N, u64)>;
+}
+
+impl<N, F> Graph<N> for F
+where
+    F: Fn(&N) -> Vec<(N, u64)>,
+{
+    fn neighbours(&self, node: &N) -> Vec<(N, u64)> {
+        self(node)
+    }
+}
+
+/// A route from the start to a goal, both ends included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path<N> {
+    pub cost: u64,
+    pub nodes: Vec<N>,
+}
+
+/// Fewest steps from `start` to a node matching `is_goal`. Step costs are
+/// ignored, so the path's cost is its number of steps.
+pub fn bfs<N, G>(graph: &G, start: N, is_goal: impl Fn(&N) -> bool) -> Option<Path<N>>
+where
+    N: Clone + Eq + Hash,
+    G: Graph<N>,
+{
+    let mut prev = HashMap::from([(start.clone(), None)]);
+    let mut queue = VecDeque::from([start]);
+
+    while let Some(node) = queue.pop_front() {
+        if is_goal(&node) {
+            let nodes = walk_back(&prev, node);
+            return Some(Path {
+                cost: nodes.len() as u64 - 1,
+                nodes,
+            });
+        }
+
+        graph.neighbours(&node).into_iter().for_each(|(next, _)| {
+            if !prev.contains_key(&next) {
+                prev.insert(next.clone(), Some(node.clone()));
+                queue.push_back(next);
+            }
+        });
+    }
+
+    None
+}
+
+/// Fewest steps from the closest of `starts` to every reachable node.
+pub fn bfs_distances<N, G>(graph: &G, starts: impl IntoIterator<Item = N>) -> HashMap<N, u64>
+where
+    N: Clone + Eq + Hash,
+    G: Graph<N>,
+{
+    let mut dist = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    starts.into_iter().for_each(|start| {
+        if !dist.contains_key(&start) {
+            dist.insert(start.clone(), 0);
+            queue.push_back(start);
+        }
+    });
+
+    while let Some(node) = queue.pop_front() {
+        let next_dist = dist[&node] + 1;
+
+        graph.neighbours(&node).into_iter().for_each(|(next, _)| {
+            if !dist.contains_key(&next) {
+                dist.insert(next.clone(), next_dist);
+                queue.push_back(next);
+            }
+        });
+    }
+
+    dist
+}
+
+/// Cheapest path from `start` to a node matching `is_goal`.
+pub fn dijkstra<N, G>(graph: &G, start: N, is_goal: impl Fn(&N) -> bool) -> Option<Path<N>>
+where
+    N: Clone + Eq + Hash + Ord,
+    G: Graph<N>,
+{
+    astar(graph, start, is_goal, |_| 0)
+}
+
+/// Cheapest cost from the closest of `starts` to every reachable node.
+pub fn dijkstra_distances<N, G>(graph: &G, starts: impl IntoIterator<Item = N>) -> HashMap<N, u64>
+where
+    N: Clone + Eq + Hash + Ord,
+    G: Graph<N>,
+{
+    let mut best = HashMap::new();
+    let mut heap = BinaryHeap::new();
+
+    starts.into_iter().for_each(|start| {
+        best.insert(start.clone(), 0);
+        heap.push(Reverse((0, start)));
+    });
+
+    while let Some(Reverse((cost, node))) = heap.pop() {
+        if cost > best[&node] {
+            continue;
+        }
+
+        graph
+            .neighbours(&node)
+            .into_iter()
+            .for_each(|(next, step)| {
+                let next_cost = cost + step;
+
+                if best.get(&next).is_none_or(|&known| next_cost < known) {
+                    best.insert(next.clone(), next_cost);
+                    heap.push(Reverse((next_cost, next)));
+                }
+            });
+    }
+
+    best
+}
+
+/// Cheapest path from `start` to a node matching `is_goal`, searching toward
+/// the goal first. The heuristic must never overestimate the remaining cost,
+/// or the path found might not be the cheapest.
+pub fn astar<N, G>(
+    graph: &G,
+    start: N,
+    is_goal: impl Fn(&N) -> bool,
+    heuristic: impl Fn(&N) -> u64,
+) -> Option<Path<N>>
+where
+    N: Clone + Eq + Hash + Ord,
+    G: Graph<N>,
+{
+    let mut best = HashMap::from([(start.clone(), 0)]);
+    let mut prev = HashMap::from([(start.clone(), None)]);
+    let mut heap = BinaryHeap::from([Reverse((heuristic(&start), 0, start))]);
+
+    while let Some(Reverse((_, cost, node))) = heap.pop() {
+        // a cheaper way here was already handled
+        if cost > best[&node] {
+            continue;
+        }
+
+        if is_goal(&node) {
+            return Some(Path {
+                cost,
+                nodes: walk_back(&prev, node),
+            });
+        }
+
+        graph
+            .neighbours(&node)
+            .into_iter()
+            .for_each(|(next, step)| {
+                let next_cost = cost + step;
+
+                if best.get(&next).is_none_or(|&known| next_cost < known) {
+                    best.insert(next.clone(), next_cost);
+                    prev.insert(next.clone(), Some(node.clone()));
+                    heap.push(Reverse((next_cost + heuristic(&next), next_cost, next)));
+                }
+            });
+    }
+
+    None
+}
+
+fn walk_back<N>(prev: &HashMap<N, Option<N>>, end: N) -> Vec<N>
+where
+    N: Clone + Eq + Hash,
+{
+    let mut nodes = vec![end];
+
+    while let Some(Some(node)) = prev.get(nodes.last().unwrap()) {
+        nodes.push(node.clone());
+    }
+
+    nodes.reverse();
+    nodes
+}